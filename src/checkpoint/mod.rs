@@ -0,0 +1,144 @@
+use super::config::SimulationConfig;
+use super::creature::genome::Genome;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[cfg(test)]
+use super::simulation::small_config;
+
+// Everything needed to continue a run from the beginning of a generation.
+// The random number generator is stored in the state it had right before the
+// generation was spawned, so a resumed run is identical to an uninterrupted one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+	pub config: SimulationConfig,
+	pub generation: u16,
+	pub gene_pool: Vec<Genome>,
+	pub rng: ChaCha8Rng,
+	// Extinctions before the generation, missing in older checkpoints
+	#[serde(default)]
+	pub extinctions: u32,
+}
+
+#[derive(Debug)]
+pub enum CheckpointError {
+	Io(PathBuf, std::io::Error),
+	Parse(PathBuf, String),
+}
+
+impl Display for CheckpointError {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		match self {
+			CheckpointError::Io(path, error) => {
+				write!(f, "Can't access checkpoint {}: {}", path.display(), error)
+			}
+			CheckpointError::Parse(path, error) => {
+				write!(f, "Can't parse checkpoint {}: {}", path.display(), error)
+			}
+		}
+	}
+}
+
+impl std::error::Error for CheckpointError {}
+
+impl Checkpoint {
+	// The checkpoint is written to a temporary file first and then renamed,
+	// so a crash while saving never leaves a truncated checkpoint behind
+	pub fn save(&self, path: &Path) -> Result<(), CheckpointError> {
+		let content = serde_json::to_string(self)
+			.map_err(|error| CheckpointError::Parse(path.to_path_buf(), error.to_string()))?;
+		if let Some(directory) = path.parent() {
+			if !directory.as_os_str().is_empty() {
+				fs::create_dir_all(directory)
+					.map_err(|error| CheckpointError::Io(path.to_path_buf(), error))?;
+			}
+		}
+		let temporary_path = path.with_extension("tmp");
+		fs::write(&temporary_path, content)
+			.map_err(|error| CheckpointError::Io(temporary_path.clone(), error))?;
+		fs::rename(&temporary_path, path)
+			.map_err(|error| CheckpointError::Io(path.to_path_buf(), error))
+	}
+
+	pub fn load(path: &Path) -> Result<Checkpoint, CheckpointError> {
+		let content = fs::read_to_string(path)
+			.map_err(|error| CheckpointError::Io(path.to_path_buf(), error))?;
+		serde_json::from_str(&content)
+			.map_err(|error| CheckpointError::Parse(path.to_path_buf(), error.to_string()))
+	}
+}
+
+#[test]
+fn should_resume_identically() {
+	use super::simulation::Simulation;
+
+	let mut simulation = Simulation::init(small_config(5)).unwrap();
+	simulation.run_generation().unwrap();
+	// A checkpoint in the middle of a generation restarts that generation
+	simulation.step_iteration();
+	let checkpoint = simulation.checkpoint();
+	assert_eq!(checkpoint.generation, 1);
+
+	let mut resumed = Simulation::resume(checkpoint).unwrap();
+	simulation.run_generation().unwrap();
+	resumed.run_generation().unwrap();
+	assert_eq!(resumed.generation(), simulation.generation());
+	assert_eq!(resumed.gene_pool(), simulation.gene_pool());
+	assert_eq!(
+		format!("{:?}", resumed.population()),
+		format!("{:?}", simulation.population())
+	);
+}
+
+#[test]
+fn should_resume_identically_from_file_with_adaptive_mutation() {
+	use super::creature::mutation::AdaptiveMutation;
+	use super::simulation::Simulation;
+
+	let path = std::env::temp_dir().join(format!(
+		"vita_checkpoint_adaptive_{}.json",
+		std::process::id()
+	));
+	let config = SimulationConfig {
+		adaptive_mutation: AdaptiveMutation {
+			enabled: true,
+			..AdaptiveMutation::default()
+		},
+		..small_config(5)
+	};
+	let mut simulation = Simulation::init(config).unwrap();
+	simulation.run_generation().unwrap();
+	simulation.checkpoint().save(&path).unwrap();
+	let checkpoint = Checkpoint::load(&path).unwrap();
+	fs::remove_file(&path).unwrap();
+	// the evolved mutation rates are saved without rounding
+	assert_eq!(checkpoint.gene_pool, simulation.gene_pool());
+
+	let mut resumed = Simulation::resume(checkpoint).unwrap();
+	simulation.run_generation().unwrap();
+	resumed.run_generation().unwrap();
+	assert_eq!(resumed.gene_pool(), simulation.gene_pool());
+	assert_eq!(
+		format!("{:?}", resumed.population()),
+		format!("{:?}", simulation.population())
+	);
+}
+
+#[test]
+fn should_save_and_load() {
+	use super::simulation::Simulation;
+
+	let path = std::env::temp_dir().join(format!("vita_checkpoint_{}.json", std::process::id()));
+	let simulation = Simulation::init(small_config(5)).unwrap();
+	simulation.checkpoint().save(&path).unwrap();
+	let checkpoint = Checkpoint::load(&path).unwrap();
+	fs::remove_file(&path).unwrap();
+
+	assert_eq!(checkpoint.config, *simulation.config());
+	assert_eq!(checkpoint.gene_pool, simulation.gene_pool());
+	assert_eq!(checkpoint.generation, 0);
+	assert!(Checkpoint::load(&path).is_err());
+}
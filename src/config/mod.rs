@@ -0,0 +1,543 @@
+use super::creature::mutation::{AdaptiveMutation, PointMutation, StructuralMutation};
+use super::creature::reproduction::ReproductionConfig;
+use super::selection::fitness::Fitness;
+use super::selection::strategy::SelectionStrategy;
+use super::selection::SelectionConfig;
+use super::simulation::extinction::ExtinctionPolicy;
+use super::world::food::{EnergyConfig, FoodConfig};
+use super::world::pheromone::PheromoneConfig;
+use super::world::{BorderPolicy, Topology};
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+// All the parameters of a simulation run.
+// Every field has a default so a config file only needs to list what it changes,
+// and every scalar field can be overridden from the command line with
+// --field-name VALUE (e.g. `--num-creatures 1000`). The tables (selection,
+// fitness, extinction, food, ...) can only be set in the config file, except for
+// a few enum-valued parameters that are exposed as their tag, see set.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SimulationConfig {
+	pub num_internal_neurons: u8,
+	pub num_genes: u8,
+	pub num_initial_gene_sequences: u16,
+
+	pub num_creatures: u16,
+	pub num_iterations: u16,
+	pub num_generations: u16,
+
+	// Every how many generations the frames are saved to disk
+	pub generation_to_save: u16,
+
+	// Chance that a single gene is mutated when a creature inherits it
+	pub mutation_chance: f32,
+
+	pub world_width: u16,
+	pub world_height: u16,
+	// Bounded (walls at the border) or toroidal (wrapping around) world
+	pub topology: Topology,
+	// Block, reflect or kill the creatures moving past the border of a bounded world
+	pub border: BorderPolicy,
+
+	// How far (in cells) creatures sense the density of the population and the
+	// nearest creature
+	pub sensor_radius: u16,
+	// How far (in cells) creatures see creatures and walls in front of them
+	pub vision_range: u16,
+
+	// Period (in iterations) of the oscillator of newborn creatures, their
+	// OscillatorPeriod output can change it
+	pub oscillator_period: u16,
+
+	// Master seed of the random number generator. Runs with the same seed and
+	// configuration are identical. When missing a random seed is picked.
+	pub seed: Option<u64>,
+
+	// Every how many generations the state of the run is saved to checkpoint_file,
+	// 0 disables checkpoints
+	pub checkpoint_interval: u16,
+	pub checkpoint_file: PathBuf,
+
+	// Gene pool file (one genome per line) the first generation is spawned from.
+	// When missing the first gene pool is random.
+	pub gene_pool_file: Option<PathBuf>,
+
+	// Map of the obstacles in the world, a PNG image or a text file, see the
+	// world map module. When missing there are no obstacles.
+	pub map_file: Option<PathBuf>,
+
+	// Which creatures survive at the end of each generation, see the selection module
+	pub selection: SelectionConfig,
+	// How each creature is scored at the end of a generation
+	pub fitness: Fitness,
+	// How the parents of the next generation are picked from the scores
+	pub selection_strategy: SelectionStrategy,
+	// What happens to the run when no creature is selected
+	pub extinction: ExtinctionPolicy,
+	// Asexual (the default) or two-parent reproduction with crossover
+	pub reproduction: ReproductionConfig,
+	// Mutations that change the length of the genomes, disabled by default
+	pub structural_mutation: StructuralMutation,
+	// Weight and rewiring mutations of single genes, disabled by default
+	pub point_mutation: PointMutation,
+	// Genomes carrying and evolving their own mutation_chance, disabled by default
+	pub adaptive_mutation: AdaptiveMutation,
+	// How the pheromone emitted by the creatures spreads and fades
+	pub pheromone: PheromoneConfig,
+	// Food items placed in the world at every generation, disabled by default
+	pub food: FoodConfig,
+	// Energy of the creatures, spent moving and gained eating
+	pub energy: EnergyConfig,
+}
+
+impl Default for SimulationConfig {
+	fn default() -> SimulationConfig {
+		SimulationConfig {
+			num_internal_neurons: 1,
+			num_genes: 10,
+			num_initial_gene_sequences: 200,
+			num_creatures: 400,
+			num_iterations: 1000,
+			num_generations: 10000,
+			generation_to_save: 100,
+			mutation_chance: 0.01f32,
+			world_width: 128,
+			world_height: 128,
+			topology: Topology::Bounded,
+			border: BorderPolicy::Block,
+			sensor_radius: 5,
+			vision_range: 10,
+			oscillator_period: 20,
+			seed: None,
+			checkpoint_interval: 100,
+			checkpoint_file: PathBuf::from("checkpoint.json"),
+			gene_pool_file: None,
+			map_file: None,
+			selection: SelectionConfig::default(),
+			fitness: Fitness::default(),
+			selection_strategy: SelectionStrategy::default(),
+			extinction: ExtinctionPolicy::default(),
+			reproduction: ReproductionConfig::default(),
+			structural_mutation: StructuralMutation::default(),
+			point_mutation: PointMutation::default(),
+			adaptive_mutation: AdaptiveMutation::default(),
+			pheromone: PheromoneConfig::default(),
+			food: FoodConfig::default(),
+			energy: EnergyConfig::default(),
+		}
+	}
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+	Io(PathBuf, std::io::Error),
+	Parse(PathBuf, String),
+	Argument(String),
+	Invalid(String),
+}
+
+impl Display for ConfigError {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		match self {
+			ConfigError::Io(path, error) => {
+				write!(f, "Can't read config file {}: {}", path.display(), error)
+			}
+			ConfigError::Parse(path, error) => {
+				write!(f, "Can't parse config file {}: {}", path.display(), error)
+			}
+			ConfigError::Argument(message) => write!(f, "Invalid argument: {}", message),
+			ConfigError::Invalid(message) => write!(f, "Invalid configuration: {}", message),
+		}
+	}
+}
+
+impl std::error::Error for ConfigError {}
+
+impl SimulationConfig {
+	// Files ending in .json are parsed as JSON, anything else as TOML
+	pub fn from_file(path: &Path) -> Result<SimulationConfig, ConfigError> {
+		let content =
+			fs::read_to_string(path).map_err(|error| ConfigError::Io(path.to_path_buf(), error))?;
+		let config = if path
+			.extension()
+			.is_some_and(|extension| extension == "json")
+		{
+			serde_json::from_str(&content)
+				.map_err(|error| ConfigError::Parse(path.to_path_buf(), error.to_string()))?
+		} else {
+			toml::from_str(&content)
+				.map_err(|error| ConfigError::Parse(path.to_path_buf(), error.to_string()))?
+		};
+		Ok(config)
+	}
+
+	// Builds the configuration from the command line arguments (without the program name).
+	// `--config <file>` is loaded first, then every other `--field-name VALUE` pair
+	// overrides the corresponding field. The result is validated.
+	pub fn from_args<I: IntoIterator<Item = String>>(
+		args: I,
+	) -> Result<SimulationConfig, ConfigError> {
+		let arguments = Arguments::parse(args)?;
+		let config = match &arguments.config {
+			Some(path) => SimulationConfig::from_file(path)?,
+			None => SimulationConfig::default(),
+		};
+		arguments.apply(config)
+	}
+
+	// Sets a single field from its command line name (dashes or underscores)
+	pub fn set(&mut self, name: &str, value: &str) -> Result<(), ConfigError> {
+		match name.replace('-', "_").as_str() {
+			"num_internal_neurons" => self.num_internal_neurons = parse_value(name, value)?,
+			"num_genes" => self.num_genes = parse_value(name, value)?,
+			"num_initial_gene_sequences" => {
+				self.num_initial_gene_sequences = parse_value(name, value)?
+			}
+			"num_creatures" => self.num_creatures = parse_value(name, value)?,
+			"num_iterations" => self.num_iterations = parse_value(name, value)?,
+			"num_generations" => self.num_generations = parse_value(name, value)?,
+			"generation_to_save" => self.generation_to_save = parse_value(name, value)?,
+			"mutation_chance" => self.mutation_chance = parse_value(name, value)?,
+			"world_width" => self.world_width = parse_value(name, value)?,
+			"world_height" => self.world_height = parse_value(name, value)?,
+			"sensor_radius" => self.sensor_radius = parse_value(name, value)?,
+			"vision_range" => self.vision_range = parse_value(name, value)?,
+			"oscillator_period" => self.oscillator_period = parse_value(name, value)?,
+			"seed" => self.seed = Some(parse_value(name, value)?),
+			"checkpoint_interval" => self.checkpoint_interval = parse_value(name, value)?,
+			"checkpoint_file" => self.checkpoint_file = PathBuf::from(value),
+			"gene_pool_file" => self.gene_pool_file = Some(PathBuf::from(value)),
+			"map_file" => self.map_file = Some(PathBuf::from(value)),
+			"crossover" => self.reproduction.crossover = parse_value(name, value)?,
+			"topology" => self.topology = parse_value(name, value)?,
+			"border" => self.border = parse_value(name, value)?,
+			"adaptive_mutation" => self.adaptive_mutation.enabled = parse_value(name, value)?,
+			_ => return Err(ConfigError::Argument(format!("unknown option --{}", name))),
+		}
+		Ok(())
+	}
+
+	// Rejects configurations that would make the simulation panic or loop forever
+	pub fn validate(&self) -> Result<(), ConfigError> {
+		if self.num_creatures == 0 {
+			return Err(ConfigError::Invalid(
+				"num_creatures must be at least 1".to_string(),
+			));
+		}
+		if self.world_width == 0 || self.world_height == 0 {
+			return Err(ConfigError::Invalid(
+				"world_width and world_height must be at least 1".to_string(),
+			));
+		}
+		let num_cells = self.world_width as u32 * self.world_height as u32;
+		if self.num_creatures as u32 > num_cells {
+			return Err(ConfigError::Invalid(format!(
+				"{} creatures don't fit in a {}x{} world",
+				self.num_creatures, self.world_width, self.world_height
+			)));
+		}
+		if self.sensor_radius == 0 {
+			return Err(ConfigError::Invalid(
+				"sensor_radius must be at least 1".to_string(),
+			));
+		}
+		if self.vision_range == 0 {
+			return Err(ConfigError::Invalid(
+				"vision_range must be at least 1".to_string(),
+			));
+		}
+		if self.oscillator_period < 2 {
+			return Err(ConfigError::Invalid(
+				"oscillator_period must be at least 2".to_string(),
+			));
+		}
+		if self.num_internal_neurons == 0 {
+			return Err(ConfigError::Invalid(
+				"num_internal_neurons must be at least 1".to_string(),
+			));
+		}
+		if self.num_genes == 0 {
+			return Err(ConfigError::Invalid(
+				"num_genes must be at least 1".to_string(),
+			));
+		}
+		if self.num_initial_gene_sequences == 0 {
+			return Err(ConfigError::Invalid(
+				"num_initial_gene_sequences must be at least 1".to_string(),
+			));
+		}
+		if self.generation_to_save == 0 {
+			return Err(ConfigError::Invalid(
+				"generation_to_save must be at least 1".to_string(),
+			));
+		}
+		if !(0f32..=1f32).contains(&self.mutation_chance) {
+			return Err(ConfigError::Invalid(
+				"mutation_chance must be between 0 and 1".to_string(),
+			));
+		}
+		self.selection.validate().map_err(ConfigError::Invalid)?;
+		self.selection_strategy
+			.validate()
+			.map_err(ConfigError::Invalid)?;
+		self.extinction.validate().map_err(ConfigError::Invalid)?;
+		// relaxing the criterion can only change a fitness computed from it
+		if matches!(self.extinction, ExtinctionPolicy::Relax { .. })
+			&& self.fitness != Fitness::Criterion
+		{
+			return Err(ConfigError::Invalid(
+				"the relax extinction policy needs the criterion fitness".to_string(),
+			));
+		}
+		self.reproduction.validate().map_err(ConfigError::Invalid)?;
+		self.structural_mutation
+			.validate()
+			.map_err(ConfigError::Invalid)?;
+		self.point_mutation
+			.validate()
+			.map_err(ConfigError::Invalid)?;
+		self.adaptive_mutation
+			.validate()
+			.map_err(ConfigError::Invalid)?;
+		self.pheromone.validate().map_err(ConfigError::Invalid)?;
+		self.food.validate().map_err(ConfigError::Invalid)?;
+		self.energy.validate().map_err(ConfigError::Invalid)?;
+		Ok(())
+	}
+}
+
+// The command line of the binary: `--config <file>` or `--resume <checkpoint>`
+// followed by any number of `--field-name VALUE` overrides
+#[derive(Debug, Default)]
+pub struct Arguments {
+	pub config: Option<PathBuf>,
+	pub resume: Option<PathBuf>,
+	overrides: Vec<(String, String)>,
+}
+
+impl Arguments {
+	pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Arguments, ConfigError> {
+		let mut arguments = Arguments::default();
+
+		let mut args = args.into_iter();
+		while let Some(arg) = args.next() {
+			let name = match arg.strip_prefix("--") {
+				Some(name) => name.to_string(),
+				None => {
+					return Err(ConfigError::Argument(format!(
+						"unexpected argument {}",
+						arg
+					)))
+				}
+			};
+			let value = match args.next() {
+				Some(value) => value,
+				None => {
+					return Err(ConfigError::Argument(format!(
+						"missing value for --{}",
+						name
+					)))
+				}
+			};
+			match name.as_str() {
+				"config" => arguments.config = Some(PathBuf::from(value)),
+				"resume" => arguments.resume = Some(PathBuf::from(value)),
+				_ => arguments.overrides.push((name, value)),
+			}
+		}
+
+		if arguments.config.is_some() && arguments.resume.is_some() {
+			return Err(ConfigError::Argument(
+				"--config can't be used with --resume, the checkpoint contains the configuration"
+					.to_string(),
+			));
+		}
+		if arguments.resume.is_some() {
+			// the checkpoint restores the random generator and the gene pool
+			if let Some((name, _)) = arguments.overrides.iter().find(|(name, _)| {
+				matches!(name.replace('-', "_").as_str(), "seed" | "gene_pool_file")
+			}) {
+				return Err(ConfigError::Argument(format!(
+					"--{} can't be used with --resume, the checkpoint contains it",
+					name
+				)));
+			}
+		}
+		Ok(arguments)
+	}
+
+	// Applies the command line overrides on top of a configuration and validates the result
+	pub fn apply(&self, mut config: SimulationConfig) -> Result<SimulationConfig, ConfigError> {
+		for (name, value) in self.overrides.iter() {
+			config.set(name, value)?;
+		}
+		config.validate()?;
+		Ok(config)
+	}
+}
+
+fn parse_value<T: FromStr>(name: &str, value: &str) -> Result<T, ConfigError> {
+	value
+		.parse()
+		.map_err(|_| ConfigError::Argument(format!("invalid value {:?} for --{}", value, name)))
+}
+
+#[cfg(test)]
+fn args(args: &[&str]) -> Vec<String> {
+	args.iter().map(|arg| arg.to_string()).collect()
+}
+
+#[test]
+fn should_accept_default_config() {
+	assert!(SimulationConfig::default().validate().is_ok());
+	assert_eq!(
+		SimulationConfig::from_args(args(&[])).unwrap(),
+		SimulationConfig::default()
+	);
+}
+
+#[test]
+fn should_override_from_args() {
+	let config =
+		SimulationConfig::from_args(args(&["--num-creatures", "10", "--mutation_chance", "0.5"]))
+			.unwrap();
+	assert_eq!(config.num_creatures, 10);
+	assert_eq!(config.mutation_chance, 0.5f32);
+	assert_eq!(config.num_genes, SimulationConfig::default().num_genes);
+	assert_eq!(config.seed, None);
+
+	let config = SimulationConfig::from_args(args(&["--seed", "42"])).unwrap();
+	assert_eq!(config.seed, Some(42));
+
+	let config = SimulationConfig::from_args(args(&["--crossover", "two-point"])).unwrap();
+	assert_eq!(
+		config.reproduction.crossover,
+		super::creature::reproduction::Crossover::TwoPoint
+	);
+
+	let config = SimulationConfig::from_args(args(&["--topology", "toroidal"])).unwrap();
+	assert_eq!(config.topology, Topology::Toroidal);
+	let config = SimulationConfig::from_args(args(&["--border", "lethal"])).unwrap();
+	assert_eq!(config.border, BorderPolicy::Lethal);
+}
+
+#[test]
+fn should_reject_bad_args() {
+	assert!(SimulationConfig::from_args(args(&["--num-creatures"])).is_err());
+	assert!(SimulationConfig::from_args(args(&["--num-creatures", "many"])).is_err());
+	assert!(SimulationConfig::from_args(args(&["--unknown", "1"])).is_err());
+	assert!(SimulationConfig::from_args(args(&["num-creatures", "1"])).is_err());
+	assert!(SimulationConfig::from_args(args(&["--crossover", "three_point"])).is_err());
+	assert!(SimulationConfig::from_args(args(&["--topology", "spherical"])).is_err());
+}
+
+#[test]
+fn should_parse_resume() {
+	let arguments =
+		Arguments::parse(args(&["--resume", "run.json", "--num-generations", "5"])).unwrap();
+	assert_eq!(arguments.resume, Some(PathBuf::from("run.json")));
+	assert_eq!(arguments.config, None);
+	let config = arguments.apply(SimulationConfig::default()).unwrap();
+	assert_eq!(config.num_generations, 5);
+
+	assert!(Arguments::parse(args(&["--resume", "run.json", "--config", "c.toml"])).is_err());
+	assert!(Arguments::parse(args(&["--resume", "run.json", "--seed", "42"])).is_err());
+	assert!(
+		Arguments::parse(args(&["--resume", "run.json", "--gene-pool-file", "pool.txt"])).is_err()
+	);
+}
+
+#[test]
+fn should_reject_zero_creatures() {
+	assert!(SimulationConfig::from_args(args(&["--num-creatures", "0"])).is_err());
+}
+
+#[test]
+fn should_reject_more_creatures_than_cells() {
+	let config = SimulationConfig {
+		num_creatures: 17,
+		world_width: 4,
+		world_height: 4,
+		..SimulationConfig::default()
+	};
+	assert!(config.validate().is_err());
+	let config = SimulationConfig {
+		num_creatures: 16,
+		..config
+	};
+	assert!(config.validate().is_ok());
+}
+
+#[test]
+fn should_parse_partial_toml() {
+	let config: SimulationConfig = toml::from_str("num_creatures = 20\nnum_genes = 4\n").unwrap();
+	assert_eq!(config.num_creatures, 20);
+	assert_eq!(config.num_genes, 4);
+	assert_eq!(
+		config.num_iterations,
+		SimulationConfig::default().num_iterations
+	);
+}
+
+#[test]
+fn should_parse_selection_table() {
+	let config: SimulationConfig =
+		toml::from_str("num_creatures = 20\n[selection]\nshape = \"edge_bands\"\nwidth = 8\n")
+			.unwrap();
+	assert_eq!(
+		config.selection.shape,
+		super::selection::Shape::EdgeBands { width: 8 }
+	);
+	assert!(config.validate().is_ok());
+}
+
+#[test]
+fn should_parse_fitness_and_selection_strategy() {
+	let config: SimulationConfig = toml::from_str(
+		"[fitness]\ntype = \"distance_to_target\"\nx = 10.0\ny = 20.0\n[selection_strategy]\ntype = \"tournament\"\nsize = 4\n",
+	)
+	.unwrap();
+	assert_eq!(
+		config.fitness,
+		Fitness::DistanceToTarget { x: 10f32, y: 20f32 }
+	);
+	assert_eq!(
+		config.selection_strategy,
+		SelectionStrategy::Tournament { size: 4 }
+	);
+	assert!(config.validate().is_ok());
+
+	let config = SimulationConfig {
+		selection_strategy: SelectionStrategy::Truncation { fraction: 0f32 },
+		..SimulationConfig::default()
+	};
+	assert!(matches!(config.validate(), Err(ConfigError::Invalid(_))));
+}
+
+#[test]
+fn should_reject_relax_without_criterion_fitness() {
+	let config = SimulationConfig {
+		extinction: ExtinctionPolicy::Relax { margin: 1 },
+		..SimulationConfig::default()
+	};
+	assert!(config.validate().is_ok());
+	let config = SimulationConfig {
+		fitness: Fitness::Energy,
+		..config
+	};
+	assert!(matches!(config.validate(), Err(ConfigError::Invalid(_))));
+}
+
+#[test]
+fn should_parse_partial_json() {
+	let config: SimulationConfig = serde_json::from_str("{\"num_iterations\": 5}").unwrap();
+	assert_eq!(config.num_iterations, 5);
+	assert_eq!(
+		config.num_creatures,
+		SimulationConfig::default().num_creatures
+	);
+}
@@ -1,26 +1,47 @@
-use super::gene::Gene;
+use super::gene::{Gene, WEIGHT_SCALE};
 use super::world;
-use super::Creature;
 use rand::prelude::*;
-use std::cmp;
 use std::collections::HashMap;
 
-pub struct BrainDescription {
-	pub num_input: u8,
-	pub num_internal: u8,
-	pub num_output: u8,
+// What a creature knows about itself and its surroundings when its input
+// neurons are set
+#[derive(Debug, Clone, Copy)]
+pub struct SensorContext {
+	pub position: world::Position,
+	pub direction: world::Direction,
+	// Displacement of the last move, zero when the move was blocked
+	pub last_movement: world::DeltaPosition,
+	// Queried once per creature and shared by the neurons that need it
+	pub neighbourhood: world::Neighbourhood,
+	// Fraction of the generation lived, in [0, 1]
+	pub age: f32,
+	// Value of the creature oscillator, in [-1, 1]
+	pub oscillator: f32,
+	// Energy of the creature, in [0, 1]
+	pub energy: f32,
 }
 
-impl BrainDescription {
-	fn init(num_internal: u8) -> BrainDescription {
-		BrainDescription {
-			num_internal,
-			num_input: INPUT_NEURONS.len() as u8,
-			num_output: OUTPUT_NEURONS.len() as u8,
+impl SensorContext {
+	// A creature in the position that didn't move yet, with full energy, alone in the world
+	pub fn init(position: world::Position, direction: world::Direction) -> SensorContext {
+		SensorContext {
+			position,
+			direction,
+			last_movement: world::DeltaPosition { x: 0f32, y: 0f32 },
+			neighbourhood: world::Neighbourhood::empty(1),
+			age: 0f32,
+			oscillator: 0f32,
+			energy: 1f32,
 		}
 	}
 }
 
+pub struct BrainDescription {
+	pub num_input: u8,
+	pub num_internal: u8,
+	pub num_output: u8,
+}
+
 #[derive(Debug)]
 pub struct Brain {
 	input: Vec<Neuron>,
@@ -70,7 +91,7 @@ impl Brain {
 		}
 	}
 
-	pub fn compute_neurons_state(&mut self, genes: &Vec<Gene>) {
+	pub fn compute_neurons_state(&mut self, genes: &[Gene]) {
 		// Reset all neurons
 		self.reset_neurons_layer(NeuronLayer::Internal);
 		self.reset_neurons_layer(NeuronLayer::Output);
@@ -103,27 +124,82 @@ impl Brain {
 		);
 	}
 
-	pub fn set_inputs(
+	pub fn set_inputs<R: Rng + ?Sized>(
 		&mut self,
 		world: &world::World,
-		position: &world::Position,
-		direction: &world::Direction,
+		context: &SensorContext,
+		rng: &mut R,
 	) {
 		for neuron in self.input.iter_mut() {
-			neuron.set_from_world(world, position, direction)
+			neuron.set_from_world(world, context, rng)
 		}
 	}
 
-	pub fn desired_move(&self, direction: &world::Direction) -> world::DeltaPosition {
+	pub fn desired_move<R: Rng + ?Sized>(
+		&self,
+		direction: &world::Direction,
+		rng: &mut R,
+	) -> world::DeltaPosition {
 		let mut delta = world::DeltaPosition { x: 0f32, y: 0f32 };
 		for neuron in self.output.iter() {
-			let delta_neuron = neuron.desired_move(direction);
+			let delta_neuron = neuron.desired_move(direction, rng);
 			delta.x += delta_neuron.x;
 			delta.y += delta_neuron.y;
 		}
 		delta
 	}
 
+	// How much the creature wants to stretch (positive) or shrink (negative)
+	// the period of its oscillator, in [-1, 1]
+	pub fn oscillator_period_change(&self) -> f32 {
+		self.output
+			.iter()
+			.filter(|neuron| neuron.neuron_type == NeuronType::OscillatorPeriod)
+			.map(|neuron| neuron.value)
+			.sum()
+	}
+
+	// How much pheromone the creature leaves in its cell, in [0, 1]
+	pub fn pheromone_emission(&self) -> f32 {
+		self.output
+			.iter()
+			.filter(|neuron| neuron.neuron_type == NeuronType::EmitPheromone)
+			.map(|neuron| neuron.value)
+			.sum::<f32>()
+			.clamp(0f32, 1f32)
+	}
+
+	// Whether any gene reads the population around the creature, so that the
+	// world is only queried when needed
+	pub fn senses_population(&self, genes: &[Gene]) -> bool {
+		let description = self.to_brain_description();
+		genes.iter().any(|gene| {
+			let source = gene.get_source_neuron(&description);
+			source.neuron_layer == NeuronLayer::Input
+				&& matches!(
+					self.input[source.neuron_number as usize].neuron_type,
+					NeuronType::PopulationDensity
+						| NeuronType::NearestDistance
+						| NeuronType::NearestForward
+						| NeuronType::NearestLeftRight
+				)
+		})
+	}
+
+	// New heading after the turn neurons fired, they cancel out when both fire
+	pub fn turn(&self, direction: &world::Direction) -> world::Direction {
+		let fired = |neuron_type: NeuronType| {
+			self.output
+				.iter()
+				.any(|neuron| neuron.neuron_type == neuron_type && neuron.fire())
+		};
+		match (fired(NeuronType::TurnLeft), fired(NeuronType::TurnRight)) {
+			(true, false) => direction.rotate_left(),
+			(false, true) => direction.rotate_right(),
+			_ => *direction,
+		}
+	}
+
 	fn reset_neurons_layer(&mut self, layer: NeuronLayer) {
 		let neurons = self.get_neurons_layer(layer);
 		for neuron in neurons.iter_mut() {
@@ -141,7 +217,7 @@ impl Brain {
 
 	fn compute_normalized_sum_on_destination_neurons(
 		&mut self,
-		connections: &Vec<NeuronConnection>,
+		connections: &[NeuronConnection],
 		source_layer: NeuronLayer,
 		destination_layer: NeuronLayer,
 	) {
@@ -186,7 +262,7 @@ impl Brain {
 		}
 	}
 
-	fn get_connection_from_genes(&self, genes: &Vec<Gene>) -> Vec<NeuronConnection> {
+	fn get_connection_from_genes(&self, genes: &[Gene]) -> Vec<NeuronConnection> {
 		let mut connections: Vec<NeuronConnection> = Vec::new();
 
 		for gene in genes {
@@ -195,7 +271,7 @@ impl Brain {
 			// weight is scaled for having smaller numbers
 			// and being able to follow the calculations by hand
 			// if something goes wrong
-			let weight = f32::from(gene.weight) / 8192f32;
+			let weight = f32::from(gene.weight) / WEIGHT_SCALE;
 			connections.push(NeuronConnection {
 				source,
 				destination,
@@ -222,7 +298,7 @@ pub enum NeuronLayer {
 	Output,
 }
 
-#[derive(Clone, Copy, std::hash::Hash, Debug)]
+#[derive(Clone, Copy, std::hash::Hash, Debug, PartialEq)]
 pub enum NeuronType {
 	// Input
 	Random,
@@ -234,6 +310,18 @@ pub enum NeuronType {
 	BorderDistanceEastWest,
 	WordLocationNorthSouth,
 	WordLocationEastWest,
+	PopulationDensity,
+	NearestDistance,
+	NearestForward,
+	NearestLeftRight,
+	Age,
+	Oscillator,
+	LookForwardCreature,
+	LookForwardWall,
+	PheromoneDensity,
+	PheromoneGradientForward,
+	Energy,
+	FoodNearby,
 
 	// Internal
 	Internal,
@@ -245,9 +333,13 @@ pub enum NeuronType {
 	MoveLeftRight,
 	MoveEastWest,
 	MoveNorthSouth,
+	TurnLeft,
+	TurnRight,
+	OscillatorPeriod,
+	EmitPheromone,
 }
 
-const INPUT_NEURONS: [NeuronType; 9] = [
+const INPUT_NEURONS: [NeuronType; 21] = [
 	NeuronType::Random,
 	NeuronType::BlockLeftRight,
 	NeuronType::BlockForward,
@@ -257,17 +349,51 @@ const INPUT_NEURONS: [NeuronType; 9] = [
 	NeuronType::BorderDistanceEastWest,
 	NeuronType::WordLocationNorthSouth,
 	NeuronType::WordLocationEastWest,
+	NeuronType::PopulationDensity,
+	NeuronType::NearestDistance,
+	NeuronType::NearestForward,
+	NeuronType::NearestLeftRight,
+	NeuronType::Age,
+	NeuronType::Oscillator,
+	NeuronType::LookForwardCreature,
+	NeuronType::LookForwardWall,
+	NeuronType::PheromoneDensity,
+	NeuronType::PheromoneGradientForward,
+	NeuronType::Energy,
+	NeuronType::FoodNearby,
 ];
 
-const OUTPUT_NEURONS: [NeuronType; 6] = [
+const OUTPUT_NEURONS: [NeuronType; 10] = [
 	NeuronType::MoveForward,
 	NeuronType::MoveRandom,
 	NeuronType::MoveReverse,
 	NeuronType::MoveLeftRight,
 	NeuronType::MoveEastWest,
 	NeuronType::MoveNorthSouth,
+	NeuronType::TurnLeft,
+	NeuronType::TurnRight,
+	NeuronType::OscillatorPeriod,
+	NeuronType::EmitPheromone,
 ];
 
+// Neuron number of an input neuron type, for tests that wire genes by hand
+#[cfg(test)]
+pub(crate) fn input_number(neuron_type: NeuronType) -> u8 {
+	INPUT_NEURONS
+		.iter()
+		.position(|input| *input == neuron_type)
+		.unwrap() as u8
+}
+
+// Neuron number of an output neuron type, for tests that wire genes by hand
+#[cfg(test)]
+pub(crate) fn output_number(neuron_type: NeuronType) -> u8 {
+	OUTPUT_NEURONS
+		.iter()
+		.position(|output| *output == neuron_type)
+		.unwrap() as u8
+}
+
 #[derive(Debug, PartialEq)]
 pub struct NeuronDescription {
 	pub neuron_layer: NeuronLayer,
@@ -282,28 +408,41 @@ pub struct Neuron {
 }
 
 impl Neuron {
-	pub fn fire(&self) {
+	pub fn neuron_type(&self) -> NeuronType {
+		self.neuron_type
+	}
+
+	pub fn neuron_layer(&self) -> NeuronLayer {
+		self.neuron_layer
+	}
+
+	pub fn value(&self) -> f32 {
+		self.value
+	}
+
+	pub fn fire(&self) -> bool {
 		// This threshold is somewhat arbitrary. TODO: tweak
-		self.value > 0.5f32;
+		self.value > 0.5f32
 	}
 
-	pub fn set_from_world(
+	pub fn set_from_world<R: Rng + ?Sized>(
 		&mut self,
 		world: &world::World,
-		position: &world::Position,
-		direction: &world::Direction,
+		context: &SensorContext,
+		rng: &mut R,
 	) {
+		let position = &context.position;
+		let direction = &context.direction;
 		match self.neuron_type {
 			NeuronType::Random => {
-				let mut rng = rand::thread_rng();
 				let random_number: f32 = rng.gen(); // Generated number uniformly distributed [0, 1)
 				self.value = random_number * 2.0 - 1.0;
 			}
 			NeuronType::BlockLeftRight => {
-				let right = position.move_direction(&direction.rotate_right(), 1, &world.boundary);
-				let left = position.move_direction(&direction.rotate_left(), 1, &world.boundary);
-				if (right.is_some() && world.coordinates.contains_key(&right.unwrap()))
-					|| (left.is_some() && world.coordinates.contains_key(&left.unwrap()))
+				let right = world.move_direction(position, &direction.rotate_right(), 1);
+				let left = world.move_direction(position, &direction.rotate_left(), 1);
+				if (right.is_some() && world.is_blocked(&right.unwrap()))
+					|| (left.is_some() && world.is_blocked(&left.unwrap()))
 				{
 					self.value = 1f32;
 				} else {
@@ -311,16 +450,28 @@ impl Neuron {
 				}
 			}
 			NeuronType::BlockForward => {
-				let forward = position.move_direction(direction, 1, &world.boundary);
-				if forward.is_some() && world.coordinates.contains_key(&forward.unwrap()) {
+				let forward = world.move_direction(position, direction, 1);
+				if forward.is_some() && world.is_blocked(&forward.unwrap()) {
 					self.value = 1f32;
 				} else {
 					self.value = 0f32;
 				}
 			}
-			// TODO: finish implementing the other input neurons
-			NeuronType::LastMovementY => {}
-			NeuronType::LastMovementX => {}
+			// A creature moves at most one cell per axis
+			NeuronType::LastMovementY => {
+				self.value = context.last_movement.y.clamp(-1f32, 1f32);
+			}
+			NeuronType::LastMovementX => {
+				self.value = context.last_movement.x.clamp(-1f32, 1f32);
+			}
+			// -1 in the center of the world and 1 on the border, a toroidal world
+			// has no border and it's always -1
+			NeuronType::BorderDistanceNorthSouth if world.topology == world::Topology::Toroidal => {
+				self.value = -1f32;
+			}
+			NeuronType::BorderDistanceEastWest if world.topology == world::Topology::Toroidal => {
+				self.value = -1f32;
+			}
 			NeuronType::BorderDistanceNorthSouth => {
 				self.value = ((world.boundary.height as f32) / 2f32 - position.y as f32).abs()
 					/ world.boundary.height as f32
@@ -337,6 +488,72 @@ impl Neuron {
 			NeuronType::WordLocationEastWest => {
 				self.value = position.x as f32 / (world.boundary.width as f32) * 2f32 - 1f32;
 			}
+			NeuronType::PopulationDensity => {
+				self.value = context.neighbourhood.density;
+			}
+			// 1 when nobody is within the sensor radius
+			NeuronType::NearestDistance => {
+				self.value = context.neighbourhood.nearest.map_or(1f32, |nearest| {
+					(nearest.length() / context.neighbourhood.radius as f32).min(1f32)
+				});
+			}
+			// Cosine of the angle between the heading and the nearest creature,
+			// 0 when nobody is within the sensor radius
+			NeuronType::NearestForward => {
+				self.value = context.neighbourhood.nearest.map_or(0f32, |nearest| {
+					nearest.dot(&direction.unit()) / nearest.length()
+				});
+			}
+			// Positive when the nearest creature is on the right
+			NeuronType::NearestLeftRight => {
+				self.value = context.neighbourhood.nearest.map_or(0f32, |nearest| {
+					nearest.dot(&direction.rotate_right().unit()) / nearest.length()
+				});
+			}
+			NeuronType::Age => {
+				self.value = context.age;
+			}
+			NeuronType::Oscillator => {
+				self.value = context.oscillator;
+			}
+			// Distance to what is seen ahead over the vision range, 1 when nothing is seen
+			NeuronType::LookForwardCreature => {
+				self.value = world
+					.look_for_creature(position, direction, world.vision_range)
+					.map_or(1f32, |distance| distance as f32 / world.vision_range as f32);
+			}
+			NeuronType::LookForwardWall => {
+				self.value = world
+					.look_for_wall(position, direction, world.vision_range)
+					.map_or(1f32, |distance| distance as f32 / world.vision_range as f32);
+			}
+			NeuronType::PheromoneDensity => {
+				self.value = world.pheromones.get(position);
+			}
+			// Pheromone in the cell ahead minus the one behind, a cell outside the
+			// world counts as the current one
+			NeuronType::PheromoneGradientForward => {
+				let pheromone_towards = |direction: &world::Direction| {
+					let cell = world
+						.move_direction(position, direction, 1)
+						.unwrap_or(*position);
+					world.pheromones.get(&cell)
+				};
+				self.value = pheromone_towards(direction)
+					- pheromone_towards(&direction.rotate_left().rotate_left());
+			}
+			NeuronType::Energy => {
+				self.value = context.energy;
+			}
+			// 1 on the food, down to 1 / (radius + 1) at the sensor radius so that far
+			// food is still told apart from no food, which is 0
+			NeuronType::FoodNearby => {
+				self.value = world
+					.nearest_food(position, world.sensor_radius)
+					.map_or(0f32, |distance| {
+						1f32 - distance / (world.sensor_radius as f32 + 1f32)
+					});
+			}
 
 			NeuronType::Internal => {}
 
@@ -346,10 +563,18 @@ impl Neuron {
 			NeuronType::MoveLeftRight => {}
 			NeuronType::MoveEastWest => {}
 			NeuronType::MoveNorthSouth => {}
+			NeuronType::TurnLeft => {}
+			NeuronType::TurnRight => {}
+			NeuronType::OscillatorPeriod => {}
+			NeuronType::EmitPheromone => {}
 		};
 	}
 
-	pub fn desired_move(&self, direction: &world::Direction) -> world::DeltaPosition {
+	pub fn desired_move<R: Rng + ?Sized>(
+		&self,
+		direction: &world::Direction,
+		rng: &mut R,
+	) -> world::DeltaPosition {
 		match self.neuron_type {
 			NeuronType::Random => world::DeltaPosition { x: 0f32, y: 0f32 },
 			NeuronType::BlockLeftRight => world::DeltaPosition { x: 0f32, y: 0f32 },
@@ -360,19 +585,28 @@ impl Neuron {
 			NeuronType::BorderDistanceEastWest => world::DeltaPosition { x: 0f32, y: 0f32 },
 			NeuronType::WordLocationNorthSouth => world::DeltaPosition { x: 0f32, y: 0f32 },
 			NeuronType::WordLocationEastWest => world::DeltaPosition { x: 0f32, y: 0f32 },
+			NeuronType::PopulationDensity => world::DeltaPosition { x: 0f32, y: 0f32 },
+			NeuronType::NearestDistance => world::DeltaPosition { x: 0f32, y: 0f32 },
+			NeuronType::NearestForward => world::DeltaPosition { x: 0f32, y: 0f32 },
+			NeuronType::NearestLeftRight => world::DeltaPosition { x: 0f32, y: 0f32 },
+			NeuronType::Age => world::DeltaPosition { x: 0f32, y: 0f32 },
+			NeuronType::Oscillator => world::DeltaPosition { x: 0f32, y: 0f32 },
+			NeuronType::LookForwardCreature => world::DeltaPosition { x: 0f32, y: 0f32 },
+			NeuronType::LookForwardWall => world::DeltaPosition { x: 0f32, y: 0f32 },
+			NeuronType::PheromoneDensity => world::DeltaPosition { x: 0f32, y: 0f32 },
+			NeuronType::PheromoneGradientForward => world::DeltaPosition { x: 0f32, y: 0f32 },
+			NeuronType::Energy => world::DeltaPosition { x: 0f32, y: 0f32 },
+			NeuronType::FoodNearby => world::DeltaPosition { x: 0f32, y: 0f32 },
 
 			NeuronType::Internal => world::DeltaPosition { x: 0f32, y: 0f32 },
 
 			// TODO: Implement output neurons
 			NeuronType::MoveForward => world::DeltaPosition { x: 0f32, y: 0f32 }
 				.move_direction(direction, self.value.max(0f32)),
-			NeuronType::MoveRandom => {
-				let mut rng = rand::thread_rng();
-				world::DeltaPosition {
-					x: rng.gen(),
-					y: rng.gen(),
-				}
-			}
+			NeuronType::MoveRandom => world::DeltaPosition {
+				x: rng.gen(),
+				y: rng.gen(),
+			},
 			NeuronType::MoveReverse => world::DeltaPosition { x: 0f32, y: 0f32 }
 				.move_direction(&direction.rotate_left().rotate_left(), self.value.max(0f32)),
 			NeuronType::MoveLeftRight => world::DeltaPosition { x: 0f32, y: 0f32 }
@@ -385,6 +619,10 @@ impl Neuron {
 				x: 0f32,
 				y: self.value,
 			},
+			NeuronType::TurnLeft => world::DeltaPosition { x: 0f32, y: 0f32 },
+			NeuronType::TurnRight => world::DeltaPosition { x: 0f32, y: 0f32 },
+			NeuronType::OscillatorPeriod => world::DeltaPosition { x: 0f32, y: 0f32 },
+			NeuronType::EmitPheromone => world::DeltaPosition { x: 0f32, y: 0f32 },
 		}
 	}
 }
@@ -397,6 +635,7 @@ struct NeuronConnection {
 }
 
 // Small value used to keep into account inaccuracies
+#[cfg(test)]
 const EPSILON: f32 = 0.01f32;
 
 #[test]
@@ -525,21 +764,48 @@ fn should_set_block_forward_true() {
 		neuron_layer: NeuronLayer::Input,
 		value: 0f32,
 	};
-	let mut world = world::World::init();
+	let mut world = world::World::init(&super::config::SimulationConfig::default());
 	let position = world::Position { x: 1, y: 1 };
 	let direction = world::Direction::North;
-	let boundary = world::Size {
-		height: 128,
-		width: 128,
-	};
 
 	assert_eq!(neuron.value, 0f32);
 
 	// one creature blocking the path forward
-	world
-		.coordinates
-		.insert(world::Position { x: 1, y: 2 }, Creature::init(0, 0));
-	neuron.set_from_world(&world, &position, &direction);
+	world.place_creature(1, &world::Position { x: 1, y: 2 });
+	neuron.set_from_world(
+		&world,
+		&SensorContext::init(position, direction),
+		&mut crate::test_rng(),
+	);
+	assert_eq!(neuron.value, 1f32);
+}
+
+#[test]
+fn should_set_block_forward_obstacle() {
+	let mut neuron = Neuron {
+		neuron_type: NeuronType::BlockForward,
+		neuron_layer: NeuronLayer::Input,
+		value: 0f32,
+	};
+	let mut world = world::World::init(&super::config::SimulationConfig::default());
+	let position = world::Position { x: 1, y: 1 };
+
+	// an obstacle blocking the path forward
+	world.obstacles.insert(world::Position { x: 2, y: 1 });
+	neuron.set_from_world(
+		&world,
+		&SensorContext::init(position, world::Direction::East),
+		&mut crate::test_rng(),
+	);
+	assert_eq!(neuron.value, 1f32);
+
+	// and on the left
+	neuron.neuron_type = NeuronType::BlockLeftRight;
+	neuron.set_from_world(
+		&world,
+		&SensorContext::init(position, world::Direction::South),
+		&mut crate::test_rng(),
+	);
 	assert_eq!(neuron.value, 1f32);
 }
 
@@ -550,14 +816,18 @@ fn should_set_block_forward_false() {
 		neuron_layer: NeuronLayer::Input,
 		value: 0f32,
 	};
-	let world = world::World::init();
+	let world = world::World::init(&super::config::SimulationConfig::default());
 	let position = world::Position { x: 1, y: 1 };
 	let direction = world::Direction::North;
 
 	assert_eq!(neuron.value, 0f32);
 
 	// nothing blocking the path forward
-	neuron.set_from_world(&world, &position, &direction);
+	neuron.set_from_world(
+		&world,
+		&SensorContext::init(position, direction),
+		&mut crate::test_rng(),
+	);
 	assert_eq!(neuron.value, 0f32);
 }
 
@@ -568,17 +838,19 @@ fn should_set_block_right_true() {
 		neuron_layer: NeuronLayer::Input,
 		value: 0f32,
 	};
-	let mut world = world::World::init();
+	let mut world = world::World::init(&super::config::SimulationConfig::default());
 	let position = world::Position { x: 1, y: 1 };
 	let direction = world::Direction::North;
 
 	assert_eq!(neuron.value, 0f32);
 
 	// one creature blocking the path left
-	world
-		.coordinates
-		.insert(world::Position { x: 2, y: 1 }, Creature::init(0, 0));
-	neuron.set_from_world(&world, &position, &direction);
+	world.place_creature(1, &world::Position { x: 2, y: 1 });
+	neuron.set_from_world(
+		&world,
+		&SensorContext::init(position, direction),
+		&mut crate::test_rng(),
+	);
 	assert_eq!(neuron.value, 1f32);
 }
 
@@ -589,17 +861,19 @@ fn should_set_block_left_true() {
 		neuron_layer: NeuronLayer::Input,
 		value: 0f32,
 	};
-	let mut world = world::World::init();
+	let mut world = world::World::init(&super::config::SimulationConfig::default());
 	let position = world::Position { x: 1, y: 1 };
 	let direction = world::Direction::North;
 
 	assert_eq!(neuron.value, 0f32);
 
 	// one creature blocking the path left
-	world
-		.coordinates
-		.insert(world::Position { x: 0, y: 1 }, Creature::init(0, 0));
-	neuron.set_from_world(&world, &position, &direction);
+	world.place_creature(1, &world::Position { x: 0, y: 1 });
+	neuron.set_from_world(
+		&world,
+		&SensorContext::init(position, direction),
+		&mut crate::test_rng(),
+	);
 	assert_eq!(neuron.value, 1f32);
 }
 
@@ -610,14 +884,18 @@ fn should_set_block_lateral_false() {
 		neuron_layer: NeuronLayer::Input,
 		value: 0f32,
 	};
-	let world = world::World::init();
+	let world = world::World::init(&super::config::SimulationConfig::default());
 	let position = world::Position { x: 1, y: 1 };
 	let direction = world::Direction::North;
 
 	assert_eq!(neuron.value, 0f32);
 
 	// nothing blocking the path laterally
-	neuron.set_from_world(&world, &position, &direction);
+	neuron.set_from_world(
+		&world,
+		&SensorContext::init(position, direction),
+		&mut crate::test_rng(),
+	);
 	assert_eq!(neuron.value, 0f32);
 }
 
@@ -628,13 +906,17 @@ fn should_set_location_north_south_beginning() {
 		neuron_layer: NeuronLayer::Input,
 		value: 0f32,
 	};
-	let world = world::World::init();
+	let world = world::World::init(&super::config::SimulationConfig::default());
 	let position = world::Position { x: 0, y: 0 };
 	let direction = world::Direction::North;
 
 	assert_eq!(neuron.value, 0f32);
 
-	neuron.set_from_world(&world, &position, &direction);
+	neuron.set_from_world(
+		&world,
+		&SensorContext::init(position, direction),
+		&mut crate::test_rng(),
+	);
 	assert_eq!(neuron.value, -1f32);
 }
 
@@ -645,7 +927,7 @@ fn should_set_location_north_south_end() {
 		neuron_layer: NeuronLayer::Input,
 		value: 0f32,
 	};
-	let world = world::World::init();
+	let world = world::World::init(&super::config::SimulationConfig::default());
 	let position = world::Position {
 		x: 0,
 		y: world.boundary.height,
@@ -654,7 +936,11 @@ fn should_set_location_north_south_end() {
 
 	assert_eq!(neuron.value, 0f32);
 
-	neuron.set_from_world(&world, &position, &direction);
+	neuron.set_from_world(
+		&world,
+		&SensorContext::init(position, direction),
+		&mut crate::test_rng(),
+	);
 	assert_eq!(neuron.value, 1f32);
 }
 
@@ -665,13 +951,17 @@ fn should_set_location_east_west_beginning() {
 		neuron_layer: NeuronLayer::Input,
 		value: 0f32,
 	};
-	let world = world::World::init();
+	let world = world::World::init(&super::config::SimulationConfig::default());
 	let position = world::Position { x: 0, y: 0 };
 	let direction = world::Direction::North;
 
 	assert_eq!(neuron.value, 0f32);
 
-	neuron.set_from_world(&world, &position, &direction);
+	neuron.set_from_world(
+		&world,
+		&SensorContext::init(position, direction),
+		&mut crate::test_rng(),
+	);
 	assert_eq!(neuron.value, -1f32);
 }
 
@@ -682,7 +972,7 @@ fn should_set_location_east_west_end() {
 		neuron_layer: NeuronLayer::Input,
 		value: 0f32,
 	};
-	let world = world::World::init();
+	let world = world::World::init(&super::config::SimulationConfig::default());
 	let position = world::Position {
 		x: world.boundary.width,
 		y: 0,
@@ -691,7 +981,11 @@ fn should_set_location_east_west_end() {
 
 	assert_eq!(neuron.value, 0f32);
 
-	neuron.set_from_world(&world, &position, &direction);
+	neuron.set_from_world(
+		&world,
+		&SensorContext::init(position, direction),
+		&mut crate::test_rng(),
+	);
 	assert_eq!(neuron.value, 1f32);
 }
 
@@ -702,13 +996,17 @@ fn should_set_border_distance_north_south_start() {
 		neuron_layer: NeuronLayer::Input,
 		value: 0f32,
 	};
-	let world = world::World::init();
+	let world = world::World::init(&super::config::SimulationConfig::default());
 	let position = world::Position { x: 0, y: 0 };
 	let direction = world::Direction::North;
 
 	assert_eq!(neuron.value, 0f32);
 
-	neuron.set_from_world(&world, &position, &direction);
+	neuron.set_from_world(
+		&world,
+		&SensorContext::init(position, direction),
+		&mut crate::test_rng(),
+	);
 	assert_eq!(neuron.value, 1f32);
 }
 
@@ -719,7 +1017,7 @@ fn should_set_border_distance_north_south_end() {
 		neuron_layer: NeuronLayer::Input,
 		value: 0f32,
 	};
-	let world = world::World::init();
+	let world = world::World::init(&super::config::SimulationConfig::default());
 	let position = world::Position {
 		x: 0,
 		y: world.boundary.height,
@@ -728,7 +1026,11 @@ fn should_set_border_distance_north_south_end() {
 
 	assert_eq!(neuron.value, 0f32);
 
-	neuron.set_from_world(&world, &position, &direction);
+	neuron.set_from_world(
+		&world,
+		&SensorContext::init(position, direction),
+		&mut crate::test_rng(),
+	);
 	assert_eq!(neuron.value, 1f32);
 }
 
@@ -739,7 +1041,7 @@ fn should_set_border_distance_north_south_middle() {
 		neuron_layer: NeuronLayer::Input,
 		value: 0f32,
 	};
-	let world = world::World::init();
+	let world = world::World::init(&super::config::SimulationConfig::default());
 	let position = world::Position {
 		x: 0,
 		y: world.boundary.height / 2u16,
@@ -748,7 +1050,11 @@ fn should_set_border_distance_north_south_middle() {
 
 	assert_eq!(neuron.value, 0f32);
 
-	neuron.set_from_world(&world, &position, &direction);
+	neuron.set_from_world(
+		&world,
+		&SensorContext::init(position, direction),
+		&mut crate::test_rng(),
+	);
 	assert_lt!(neuron.value, -1f32 + 2f32 * EPSILON);
 }
 
@@ -759,13 +1065,17 @@ fn should_set_border_distance_east_west_start() {
 		neuron_layer: NeuronLayer::Input,
 		value: 0f32,
 	};
-	let world = world::World::init();
+	let world = world::World::init(&super::config::SimulationConfig::default());
 	let position = world::Position { x: 0, y: 0 };
 	let direction = world::Direction::North;
 
 	assert_eq!(neuron.value, 0f32);
 
-	neuron.set_from_world(&world, &position, &direction);
+	neuron.set_from_world(
+		&world,
+		&SensorContext::init(position, direction),
+		&mut crate::test_rng(),
+	);
 	assert_eq!(neuron.value, 1f32);
 }
 
@@ -776,7 +1086,7 @@ fn should_set_border_distance_east_west_end() {
 		neuron_layer: NeuronLayer::Input,
 		value: 0f32,
 	};
-	let world = world::World::init();
+	let world = world::World::init(&super::config::SimulationConfig::default());
 	let position = world::Position {
 		x: world.boundary.width,
 		y: 0,
@@ -785,7 +1095,11 @@ fn should_set_border_distance_east_west_end() {
 
 	assert_eq!(neuron.value, 0f32);
 
-	neuron.set_from_world(&world, &position, &direction);
+	neuron.set_from_world(
+		&world,
+		&SensorContext::init(position, direction),
+		&mut crate::test_rng(),
+	);
 	assert_eq!(neuron.value, 1f32);
 }
 
@@ -796,7 +1110,7 @@ fn should_set_border_distance_east_west_middle() {
 		neuron_layer: NeuronLayer::Input,
 		value: 0f32,
 	};
-	let world = world::World::init();
+	let world = world::World::init(&super::config::SimulationConfig::default());
 	let position = world::Position {
 		x: world.boundary.width / 2u16,
 		y: 0,
@@ -805,7 +1119,11 @@ fn should_set_border_distance_east_west_middle() {
 
 	assert_eq!(neuron.value, 0f32);
 
-	neuron.set_from_world(&world, &position, &direction);
+	neuron.set_from_world(
+		&world,
+		&SensorContext::init(position, direction),
+		&mut crate::test_rng(),
+	);
 	assert_lt!(neuron.value, -1f32 + 2f32 * EPSILON);
 }
 
@@ -822,7 +1140,7 @@ fn should_want_move_forward() {
 	};
 
 	assert_eq!(
-		neuron.desired_move(&world::Direction::North),
+		neuron.desired_move(&world::Direction::North, &mut crate::test_rng()),
 		world::DeltaPosition { x: 0f32, y: 1f32 }
 	);
 }
@@ -836,7 +1154,7 @@ fn should_want_move_not_forward() {
 	};
 
 	assert_eq!(
-		neuron.desired_move(&world::Direction::North),
+		neuron.desired_move(&world::Direction::North, &mut crate::test_rng()),
 		world::DeltaPosition { x: 0f32, y: 0f32 }
 	);
 }
@@ -850,7 +1168,7 @@ fn should_want_move_never_backward() {
 	};
 
 	assert_eq!(
-		neuron.desired_move(&world::Direction::North),
+		neuron.desired_move(&world::Direction::North, &mut crate::test_rng()),
 		world::DeltaPosition { x: 0f32, y: 0f32 }
 	);
 }
@@ -863,7 +1181,7 @@ fn should_move_randomly() {
 		value: 1f32,
 	};
 
-	let delta = neuron.desired_move(&world::Direction::North);
+	let delta = neuron.desired_move(&world::Direction::North, &mut crate::test_rng());
 	assert_le!(delta.x, 1f32);
 	assert_ge!(delta.x, -1f32);
 	assert_le!(delta.y, 1f32);
@@ -879,7 +1197,7 @@ fn should_want_move_reverse() {
 	};
 
 	assert_eq!(
-		neuron.desired_move(&world::Direction::North),
+		neuron.desired_move(&world::Direction::North, &mut crate::test_rng()),
 		world::DeltaPosition { x: 0f32, y: -1f32 }
 	);
 }
@@ -893,7 +1211,7 @@ fn should_want_move_reverse_never_forward() {
 	};
 
 	assert_eq!(
-		neuron.desired_move(&world::Direction::North),
+		neuron.desired_move(&world::Direction::North, &mut crate::test_rng()),
 		world::DeltaPosition { x: 0f32, y: 0f32 }
 	);
 }
@@ -907,7 +1225,7 @@ fn should_want_move_right() {
 	};
 
 	assert_eq!(
-		neuron.desired_move(&world::Direction::North),
+		neuron.desired_move(&world::Direction::North, &mut crate::test_rng()),
 		world::DeltaPosition { x: 1f32, y: 0f32 }
 	);
 }
@@ -921,7 +1239,7 @@ fn should_want_move_left() {
 	};
 
 	assert_eq!(
-		neuron.desired_move(&world::Direction::North),
+		neuron.desired_move(&world::Direction::North, &mut crate::test_rng()),
 		world::DeltaPosition { x: -1f32, y: 0f32 }
 	);
 }
@@ -935,7 +1253,7 @@ fn should_want_to_not_move_laterally() {
 	};
 
 	assert_eq!(
-		neuron.desired_move(&world::Direction::North),
+		neuron.desired_move(&world::Direction::North, &mut crate::test_rng()),
 		world::DeltaPosition { x: 0f32, y: 0f32 }
 	);
 }
@@ -949,7 +1267,7 @@ fn should_want_to_move_east() {
 	};
 
 	assert_eq!(
-		neuron.desired_move(&world::Direction::North),
+		neuron.desired_move(&world::Direction::North, &mut crate::test_rng()),
 		world::DeltaPosition { x: 1f32, y: 0f32 }
 	);
 }
@@ -963,7 +1281,7 @@ fn should_want_to_move_west() {
 	};
 
 	assert_eq!(
-		neuron.desired_move(&world::Direction::North),
+		neuron.desired_move(&world::Direction::North, &mut crate::test_rng()),
 		world::DeltaPosition { x: -1f32, y: 0f32 }
 	);
 }
@@ -977,7 +1295,7 @@ fn should_want_to_not_move_east_west() {
 	};
 
 	assert_eq!(
-		neuron.desired_move(&world::Direction::North),
+		neuron.desired_move(&world::Direction::North, &mut crate::test_rng()),
 		world::DeltaPosition { x: 0f32, y: 0f32 }
 	);
 }
@@ -991,7 +1309,7 @@ fn should_want_to_move_north() {
 	};
 
 	assert_eq!(
-		neuron.desired_move(&world::Direction::North),
+		neuron.desired_move(&world::Direction::North, &mut crate::test_rng()),
 		world::DeltaPosition { x: 0f32, y: 1f32 }
 	);
 }
@@ -1005,7 +1323,7 @@ fn should_want_to_move_south() {
 	};
 
 	assert_eq!(
-		neuron.desired_move(&world::Direction::North),
+		neuron.desired_move(&world::Direction::North, &mut crate::test_rng()),
 		world::DeltaPosition { x: 0f32, y: -1f32 }
 	);
 }
@@ -1019,7 +1337,252 @@ fn should_want_to_not_move_north_south() {
 	};
 
 	assert_eq!(
-		neuron.desired_move(&world::Direction::North),
+		neuron.desired_move(&world::Direction::North, &mut crate::test_rng()),
 		world::DeltaPosition { x: 0f32, y: 0f32 }
 	);
 }
+
+#[test]
+fn should_set_last_movement() {
+	let mut neuron_x = Neuron {
+		neuron_type: NeuronType::LastMovementX,
+		neuron_layer: NeuronLayer::Input,
+		value: 0f32,
+	};
+	let mut neuron_y = Neuron {
+		neuron_type: NeuronType::LastMovementY,
+		neuron_layer: NeuronLayer::Input,
+		value: 0f32,
+	};
+	let world = world::World::init(&super::config::SimulationConfig::default());
+	let context = SensorContext {
+		last_movement: world::DeltaPosition { x: -1f32, y: 1f32 },
+		..SensorContext::init(world::Position { x: 1, y: 1 }, world::Direction::North)
+	};
+
+	neuron_x.set_from_world(&world, &context, &mut crate::test_rng());
+	neuron_y.set_from_world(&world, &context, &mut crate::test_rng());
+	assert_eq!(neuron_x.value, -1f32);
+	assert_eq!(neuron_y.value, 1f32);
+
+	// a blocked move is no movement
+	let context = SensorContext::init(world::Position { x: 1, y: 1 }, world::Direction::North);
+	neuron_x.set_from_world(&world, &context, &mut crate::test_rng());
+	neuron_y.set_from_world(&world, &context, &mut crate::test_rng());
+	assert_eq!(neuron_x.value, 0f32);
+	assert_eq!(neuron_y.value, 0f32);
+}
+
+#[test]
+fn should_turn() {
+	let mut brain = Brain::init(1);
+	let turn_left = brain
+		.output
+		.iter()
+		.position(|neuron| neuron.neuron_type == NeuronType::TurnLeft)
+		.unwrap();
+	let turn_right = brain
+		.output
+		.iter()
+		.position(|neuron| neuron.neuron_type == NeuronType::TurnRight)
+		.unwrap();
+	assert_eq!(
+		brain.turn(&world::Direction::North),
+		world::Direction::North
+	);
+
+	brain.output[turn_left].value = 1f32;
+	assert_eq!(brain.turn(&world::Direction::North), world::Direction::West);
+
+	brain.output[turn_right].value = 1f32;
+	assert_eq!(
+		brain.turn(&world::Direction::North),
+		world::Direction::North
+	);
+
+	brain.output[turn_left].value = 0f32;
+	assert_eq!(brain.turn(&world::Direction::North), world::Direction::East);
+}
+
+#[test]
+fn should_set_population_sensors() {
+	let mut world = world::World::init(&super::config::SimulationConfig::default());
+	let position = world::Position { x: 10, y: 10 };
+	// heading north with a creature 3 cells to the east, on the right
+	world.place_creature(1, &world::Position { x: 13, y: 10 });
+	let context = SensorContext {
+		neighbourhood: world.neighbourhood(&position, 5),
+		..SensorContext::init(position, world::Direction::North)
+	};
+	let sense = |neuron_type: NeuronType| {
+		let mut neuron = Neuron {
+			neuron_type,
+			neuron_layer: NeuronLayer::Input,
+			value: 0f32,
+		};
+		neuron.set_from_world(&world, &context, &mut crate::test_rng());
+		neuron.value
+	};
+	assert_gt!(sense(NeuronType::PopulationDensity), 0f32);
+	assert_lt!(sense(NeuronType::PopulationDensity), 0.1f32);
+	assert_eq!(sense(NeuronType::NearestDistance), 0.6f32);
+	assert_eq!(sense(NeuronType::NearestForward), 0f32);
+	assert_eq!(sense(NeuronType::NearestLeftRight), 1f32);
+
+	// alone in the world
+	let context = SensorContext::init(position, world::Direction::North);
+	let mut neuron = Neuron {
+		neuron_type: NeuronType::NearestDistance,
+		neuron_layer: NeuronLayer::Input,
+		value: 0f32,
+	};
+	neuron.set_from_world(&world, &context, &mut crate::test_rng());
+	assert_eq!(neuron.value, 1f32);
+}
+
+#[test]
+fn should_sense_population_only_when_connected() {
+	let brain = Brain::init(1);
+	let blind = [Gene::init(NeuronLayer::Input, 0, NeuronLayer::Output, 0, 1)];
+	let social = [Gene::init(
+		NeuronLayer::Input,
+		input_number(NeuronType::PopulationDensity),
+		NeuronLayer::Output,
+		0,
+		1,
+	)];
+	assert!(!brain.senses_population(&blind));
+	assert!(brain.senses_population(&social));
+}
+
+#[test]
+fn should_set_age_and_oscillator() {
+	let world = world::World::init(&super::config::SimulationConfig::default());
+	let context = SensorContext {
+		age: 0.25f32,
+		oscillator: -0.5f32,
+		..SensorContext::init(world::Position { x: 1, y: 1 }, world::Direction::North)
+	};
+	let mut age = Neuron {
+		neuron_type: NeuronType::Age,
+		neuron_layer: NeuronLayer::Input,
+		value: 0f32,
+	};
+	let mut oscillator = Neuron {
+		neuron_type: NeuronType::Oscillator,
+		neuron_layer: NeuronLayer::Input,
+		value: 0f32,
+	};
+	age.set_from_world(&world, &context, &mut crate::test_rng());
+	oscillator.set_from_world(&world, &context, &mut crate::test_rng());
+	assert_eq!(age.value, 0.25f32);
+	assert_eq!(oscillator.value, -0.5f32);
+}
+
+#[test]
+fn should_look_forward() {
+	let mut world = world::World::init(&super::config::SimulationConfig::default());
+	let position = world::Position { x: 1, y: 1 };
+	world.place_creature(1, &world::Position { x: 1, y: 6 });
+	let look = |neuron_type: NeuronType, direction: world::Direction| {
+		let mut neuron = Neuron {
+			neuron_type,
+			neuron_layer: NeuronLayer::Input,
+			value: 0f32,
+		};
+		neuron.set_from_world(
+			&world,
+			&SensorContext::init(position, direction),
+			&mut crate::test_rng(),
+		);
+		neuron.value
+	};
+	// the default vision range is 10
+	assert_eq!(
+		look(NeuronType::LookForwardCreature, world::Direction::North),
+		0.5f32
+	);
+	assert_eq!(
+		look(NeuronType::LookForwardCreature, world::Direction::East),
+		1f32
+	);
+	assert_eq!(
+		look(NeuronType::LookForwardWall, world::Direction::South),
+		0.2f32
+	);
+	assert_eq!(
+		look(NeuronType::LookForwardWall, world::Direction::North),
+		1f32
+	);
+}
+
+#[test]
+fn should_sense_pheromones() {
+	let mut world = world::World::init(&super::config::SimulationConfig::default());
+	let position = world::Position { x: 5, y: 5 };
+	world.pheromones.deposit(&position, 1f32);
+	world
+		.pheromones
+		.deposit(&world::Position { x: 6, y: 5 }, 0.5f32);
+	let sense = |neuron_type: NeuronType, direction: world::Direction| {
+		let mut neuron = Neuron {
+			neuron_type,
+			neuron_layer: NeuronLayer::Input,
+			value: 0f32,
+		};
+		neuron.set_from_world(
+			&world,
+			&SensorContext::init(position, direction),
+			&mut crate::test_rng(),
+		);
+		neuron.value
+	};
+	// the default deposit is 0.5
+	assert_eq!(
+		sense(NeuronType::PheromoneDensity, world::Direction::North),
+		0.5f32
+	);
+	assert_eq!(
+		sense(NeuronType::PheromoneGradientForward, world::Direction::East),
+		0.25f32
+	);
+	assert_eq!(
+		sense(NeuronType::PheromoneGradientForward, world::Direction::West),
+		-0.25f32
+	);
+	assert_eq!(
+		sense(
+			NeuronType::PheromoneGradientForward,
+			world::Direction::North
+		),
+		0f32
+	);
+}
+
+#[test]
+fn should_sense_energy_and_food() {
+	let mut world = world::World::init(&super::config::SimulationConfig::default());
+	let position = world::Position { x: 5, y: 5 };
+	let sense = |neuron_type: NeuronType, world: &world::World| {
+		let mut neuron = Neuron {
+			neuron_type,
+			neuron_layer: NeuronLayer::Input,
+			value: 0f32,
+		};
+		let context = SensorContext {
+			energy: 0.3f32,
+			..SensorContext::init(position, world::Direction::North)
+		};
+		neuron.set_from_world(world, &context, &mut crate::test_rng());
+		neuron.value
+	};
+	assert_eq!(sense(NeuronType::Energy, &world), 0.3f32);
+	assert_eq!(sense(NeuronType::FoodNearby, &world), 0f32);
+	// the default sensor radius is 5, food outside of it isn't sensed
+	world.food.insert(world::Position { x: 5, y: 11 });
+	assert_eq!(sense(NeuronType::FoodNearby, &world), 0f32);
+	world.food.insert(world::Position { x: 8, y: 5 });
+	assert_eq!(sense(NeuronType::FoodNearby, &world), 0.5f32);
+	world.food.insert(position);
+	assert_eq!(sense(NeuronType::FoodNearby, &world), 1f32);
+}
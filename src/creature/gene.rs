@@ -1,8 +1,12 @@
 use super::brain::{BrainDescription, NeuronDescription, NeuronLayer};
 use rand::prelude::*;
 use std::fmt::{self, Debug, Display, Formatter};
+use std::str::FromStr;
 
-#[derive(Clone, Copy)]
+// The brain divides the weight of a gene by this, so that connection weights are about in [-4, 4]
+pub const WEIGHT_SCALE: f32 = 8192f32;
+
+#[derive(Clone, Copy, PartialEq)]
 pub struct Gene {
 	// source neuron
 	source: u8,
@@ -29,6 +33,44 @@ impl Display for Gene {
 	}
 }
 
+// Error returned when a gene is not made of exactly 8 hexadecimal digits
+#[derive(Debug, PartialEq)]
+pub struct ParseGeneError {
+	text: String,
+}
+
+impl Display for ParseGeneError {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		write!(
+			f,
+			"invalid gene {:?}: expected 8 hexadecimal digits",
+			self.text
+		)
+	}
+}
+
+impl std::error::Error for ParseGeneError {}
+
+// Parses the format printed by Display: source, destination and weight as hex digits
+impl FromStr for Gene {
+	type Err = ParseGeneError;
+
+	fn from_str(s: &str) -> Result<Gene, ParseGeneError> {
+		let error = || ParseGeneError {
+			text: s.to_string(),
+		};
+		if s.len() != 8 || !s.chars().all(|c| c.is_ascii_hexdigit()) {
+			return Err(error());
+		}
+		let raw_gene = u32::from_str_radix(s, 16).map_err(|_| error())?;
+		Ok(Gene {
+			source: (raw_gene >> (16 + 8)) as u8,
+			destination: (raw_gene >> 16) as u8,
+			weight: raw_gene as i16,
+		})
+	}
+}
+
 impl Gene {
 	pub fn init(
 		source_layer: NeuronLayer,
@@ -44,24 +86,23 @@ impl Gene {
 			panic!("input neuron can't be a connection destination");
 		}
 		let source = match source_layer {
-			NeuronLayer::Input => 0b00000000 | source_number,
+			NeuronLayer::Input => source_number,
 			NeuronLayer::Internal => 0b10000000 | source_number,
 			NeuronLayer::Output => 0,
 		};
 		let destination = match destination_layer {
 			NeuronLayer::Input => 0,
-			NeuronLayer::Internal => 0b00000000 | destination_number,
+			NeuronLayer::Internal => destination_number,
 			NeuronLayer::Output => 0b10000000 | destination_number,
 		};
-		return Gene {
+		Gene {
 			source,
 			destination,
 			weight,
-		};
+		}
 	}
 
-	pub fn init_random() -> Gene {
-		let mut rng = rand::thread_rng();
+	pub fn init_random<R: Rng + ?Sized>(rng: &mut R) -> Gene {
 		Gene {
 			source: rng.gen(),
 			destination: rng.gen(),
@@ -71,17 +112,17 @@ impl Gene {
 
 	pub fn get_source_neuron_layer(&self) -> NeuronLayer {
 		if self.source & 0b10000000 == 0 {
-			return NeuronLayer::Input;
+			NeuronLayer::Input
 		} else {
-			return NeuronLayer::Internal;
+			NeuronLayer::Internal
 		}
 	}
 
 	pub fn get_destination_neuron_layer(&self) -> NeuronLayer {
 		if self.destination & 0b10000000 == 0 {
-			return NeuronLayer::Internal;
+			NeuronLayer::Internal
 		} else {
-			return NeuronLayer::Output;
+			NeuronLayer::Output
 		}
 	}
 
@@ -95,6 +136,15 @@ impl Gene {
 		Gene::get_neuron(neuron_layer, self.destination, brain)
 	}
 
+	// Connects the gene to a random source or destination neuron, the weight is kept
+	pub fn rewire<R: Rng + ?Sized>(&mut self, rng: &mut R) {
+		if rng.gen::<bool>() {
+			self.source = rng.gen();
+		} else {
+			self.destination = rng.gen();
+		}
+	}
+
 	pub fn mutate(&mut self, bit: u8) {
 		if bit >= 32 {
 			panic!()
@@ -242,6 +292,33 @@ fn should_display_correctly() {
 	);
 }
 
+#[test]
+fn should_parse_displayed_gene() {
+	for text in [
+		"00000000", "FFFFFFFF", "FF00FFFF", "00FFFFFF", "8001C000", "0a1b2c3d",
+	] {
+		let gene: Gene = text.parse().unwrap();
+		assert_eq!(format!("{}", gene), text.to_uppercase());
+	}
+	assert_eq!(
+		"FF00FFFF".parse::<Gene>().unwrap(),
+		Gene::init(NeuronLayer::Internal, 127, NeuronLayer::Internal, 0, -1)
+	);
+}
+
+#[test]
+fn should_reject_invalid_gene() {
+	assert!("".parse::<Gene>().is_err());
+	assert!("0000000".parse::<Gene>().is_err());
+	assert!("000000000".parse::<Gene>().is_err());
+	assert!("0000000G".parse::<Gene>().is_err());
+	assert!("+0000000".parse::<Gene>().is_err());
+	assert_eq!(
+		format!("{}", "XYZ".parse::<Gene>().unwrap_err()),
+		"invalid gene \"XYZ\": expected 8 hexadecimal digits"
+	);
+}
+
 #[test]
 fn should_mutate() {
 	fn init_and_mutate(bit: u8) -> Gene {
@@ -256,3 +333,16 @@ fn should_mutate() {
 	assert_eq!(format!("{}", init_and_mutate(4)), "00000010");
 	assert_eq!(format!("{}", init_and_mutate(31)), "80000000");
 }
+
+#[test]
+fn should_rewire_keeping_weight() {
+	let mut rng = crate::test_rng();
+	let original = Gene::init(NeuronLayer::Input, 0, NeuronLayer::Internal, 0, 1234);
+	for _ in 0..20 {
+		let mut gene = original;
+		gene.rewire(&mut rng);
+		assert_eq!(gene.weight, 1234);
+		// only one end of the connection can change
+		assert!(gene.source == original.source || gene.destination == original.destination);
+	}
+}
@@ -0,0 +1,262 @@
+use super::gene::{Gene, ParseGeneError};
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Debug, Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+// The full set of genes of a creature.
+// It's printed (and parsed) as the genes in hex separated by spaces,
+// followed by the mutation rate as `m=0.01` when the genome has one.
+// The same format is used to serialize it, so the rate is printed with all the
+// digits needed to read back the same value.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub struct Genome {
+	pub genes: Vec<Gene>,
+	// Only with adaptive mutation, the chance of every gene of the children
+	// to get a bit flipped
+	pub mutation_rate: Option<f32>,
+}
+
+impl Display for Genome {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		write!(
+			f,
+			"{}",
+			self.genes
+				.iter()
+				.map(|gene| format!("{}", gene))
+				.collect::<Vec<String>>()
+				.join(" ")
+		)?;
+		if let Some(mutation_rate) = self.mutation_rate {
+			write!(f, " m={}", mutation_rate)?;
+		}
+		Ok(())
+	}
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ParseGenomeError {
+	Empty,
+	// position is the index of the invalid gene in the genome
+	Gene {
+		position: usize,
+		error: ParseGeneError,
+	},
+	MutationRate(String),
+}
+
+impl Display for ParseGenomeError {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		match self {
+			ParseGenomeError::Empty => write!(f, "genome without genes"),
+			ParseGenomeError::Gene { position, error } => {
+				write!(f, "gene {}: {}", position + 1, error)
+			}
+			ParseGenomeError::MutationRate(text) => {
+				write!(
+					f,
+					"invalid mutation rate {:?}: expected a number between 0 and 1",
+					text
+				)
+			}
+		}
+	}
+}
+
+impl std::error::Error for ParseGenomeError {}
+
+impl FromStr for Genome {
+	type Err = ParseGenomeError;
+
+	fn from_str(s: &str) -> Result<Genome, ParseGenomeError> {
+		let mut genes: Vec<Gene> = Vec::new();
+		let mut mutation_rate = None;
+		for text in s.split_whitespace() {
+			if let Some(rate) = text.strip_prefix("m=") {
+				let error = || ParseGenomeError::MutationRate(rate.to_string());
+				let value: f32 = rate.parse().map_err(|_| error())?;
+				if !(0f32..=1f32).contains(&value) {
+					return Err(error());
+				}
+				mutation_rate = Some(value);
+				continue;
+			}
+			// the mutation rate doesn't count as a position
+			let position = genes.len();
+			genes.push(
+				text.parse()
+					.map_err(|error| ParseGenomeError::Gene { position, error })?,
+			);
+		}
+		if genes.is_empty() {
+			return Err(ParseGenomeError::Empty);
+		}
+		Ok(Genome {
+			genes,
+			mutation_rate,
+		})
+	}
+}
+
+impl From<Genome> for String {
+	fn from(genome: Genome) -> String {
+		format!("{}", genome)
+	}
+}
+
+impl TryFrom<String> for Genome {
+	type Error = ParseGenomeError;
+
+	fn try_from(s: String) -> Result<Genome, ParseGenomeError> {
+		s.parse()
+	}
+}
+
+impl Genome {
+	pub fn init_random<R: Rng + ?Sized>(num_genes: u8, rng: &mut R) -> Genome {
+		Genome {
+			genes: (0..num_genes).map(|_| Gene::init_random(rng)).collect(),
+			mutation_rate: None,
+		}
+	}
+}
+
+#[derive(Debug)]
+pub enum GenePoolError {
+	Io(PathBuf, std::io::Error),
+	Parse {
+		path: PathBuf,
+		line: usize,
+		error: ParseGenomeError,
+	},
+	Empty(PathBuf),
+}
+
+impl Display for GenePoolError {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		match self {
+			GenePoolError::Io(path, error) => {
+				write!(f, "Can't access gene pool {}: {}", path.display(), error)
+			}
+			GenePoolError::Parse { path, line, error } => {
+				write!(f, "{}:{}: {}", path.display(), line, error)
+			}
+			GenePoolError::Empty(path) => {
+				write!(f, "Gene pool {} has no genomes", path.display())
+			}
+		}
+	}
+}
+
+impl std::error::Error for GenePoolError {}
+
+// A gene pool file has one genome per line.
+// Empty lines and lines starting with # are ignored.
+pub fn read_gene_pool(path: &Path) -> Result<Vec<Genome>, GenePoolError> {
+	let content =
+		fs::read_to_string(path).map_err(|error| GenePoolError::Io(path.to_path_buf(), error))?;
+	let gene_pool = parse_gene_pool(&content).map_err(|(line, error)| GenePoolError::Parse {
+		path: path.to_path_buf(),
+		line,
+		error,
+	})?;
+	if gene_pool.is_empty() {
+		return Err(GenePoolError::Empty(path.to_path_buf()));
+	}
+	Ok(gene_pool)
+}
+
+pub fn write_gene_pool(path: &Path, gene_pool: &[Genome]) -> Result<(), GenePoolError> {
+	let mut content = String::new();
+	for genome in gene_pool.iter() {
+		content.push_str(&format!("{}\n", genome));
+	}
+	fs::write(path, content).map_err(|error| GenePoolError::Io(path.to_path_buf(), error))
+}
+
+// On error returns the line number (starting from 1) together with the error
+fn parse_gene_pool(content: &str) -> Result<Vec<Genome>, (usize, ParseGenomeError)> {
+	let mut gene_pool: Vec<Genome> = Vec::new();
+	for (index, line) in content.lines().enumerate() {
+		let line = line.trim();
+		if line.is_empty() || line.starts_with('#') {
+			continue;
+		}
+		gene_pool.push(line.parse().map_err(|error| (index + 1, error))?);
+	}
+	Ok(gene_pool)
+}
+
+#[test]
+fn should_parse_displayed_genome() {
+	let genome: Genome = "00000000 FFFFFFFF 8001C000".parse().unwrap();
+	assert_eq!(genome.genes.len(), 3);
+	assert_eq!(format!("{}", genome), "00000000 FFFFFFFF 8001C000");
+
+	let mut rng = crate::test_rng();
+	let genome = Genome::init_random(10, &mut rng);
+	assert_eq!(format!("{}", genome).parse::<Genome>().unwrap(), genome);
+}
+
+#[test]
+fn should_parse_mutation_rate() {
+	let genome: Genome = "00000000 FFFFFFFF m=0.0125".parse().unwrap();
+	assert_eq!(genome.genes.len(), 2);
+	assert_eq!(genome.mutation_rate, Some(0.0125f32));
+	assert_eq!(format!("{}", genome), "00000000 FFFFFFFF m=0.0125");
+	assert_eq!(format!("{}", genome).parse::<Genome>().unwrap(), genome);
+	// rates from adaptive mutation have more digits
+	let genome: Genome = "00000000 m=0.012345678".parse().unwrap();
+	assert_eq!(format!("{}", genome).parse::<Genome>().unwrap(), genome);
+
+	assert_eq!(
+		"00000000 m=2".parse::<Genome>(),
+		Err(ParseGenomeError::MutationRate("2".to_string()))
+	);
+	assert_eq!("m=0.5".parse::<Genome>(), Err(ParseGenomeError::Empty));
+}
+
+#[test]
+fn should_reject_invalid_genome() {
+	assert_eq!("".parse::<Genome>(), Err(ParseGenomeError::Empty));
+	assert_eq!("  ".parse::<Genome>(), Err(ParseGenomeError::Empty));
+	let error = "00000000 0000000Z".parse::<Genome>().unwrap_err();
+	assert_eq!(
+		format!("{}", error),
+		"gene 2: invalid gene \"0000000Z\": expected 8 hexadecimal digits"
+	);
+	// the rate is not a gene, even when it comes first
+	let error = "m=0.01 00000000 0000000Z".parse::<Genome>().unwrap_err();
+	assert!(matches!(error, ParseGenomeError::Gene { position: 1, .. }));
+}
+
+#[test]
+fn should_parse_gene_pool() {
+	let gene_pool = parse_gene_pool("# survivors\n00000000 FFFFFFFF\n\n8001C000\n").unwrap();
+	assert_eq!(gene_pool.len(), 2);
+	assert_eq!(gene_pool[0].genes.len(), 2);
+	assert_eq!(gene_pool[1].genes.len(), 1);
+
+	let (line, _) = parse_gene_pool("00000000\n\n0000\n").unwrap_err();
+	assert_eq!(line, 3);
+}
+
+#[test]
+fn should_write_and_read_gene_pool() {
+	let path = std::env::temp_dir().join(format!("vita_gene_pool_{}.txt", std::process::id()));
+	let mut rng = crate::test_rng();
+	let gene_pool: Vec<Genome> = (0..5).map(|_| Genome::init_random(4, &mut rng)).collect();
+	write_gene_pool(&path, &gene_pool).unwrap();
+	assert_eq!(read_gene_pool(&path).unwrap(), gene_pool);
+
+	fs::write(&path, "# nothing here\n").unwrap();
+	assert!(matches!(
+		read_gene_pool(&path),
+		Err(GenePoolError::Empty(_))
+	));
+	fs::remove_file(&path).unwrap();
+}
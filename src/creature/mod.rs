@@ -1,63 +1,77 @@
+use super::config;
 use super::world;
 use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
 use std::fmt::{self, Debug, Display, Formatter};
 
 pub mod brain;
 pub mod gene;
-
-const MUTATION_CHANCE: f32 = 0.01f32;
+pub mod genome;
+pub mod mutation;
+pub mod reproduction;
 
 #[derive(Debug, Clone)]
 pub struct Creature {
 	pub brain: brain::Brain,
-	pub genes: Vec<gene::Gene>,
+	pub genome: genome::Genome,
 	pub position: world::Position,
 	direction: world::Direction,
+	// Displacement of the last move, zero when the world blocked it
+	pub last_movement: world::DeltaPosition,
+	// Iterations lived, out of the iterations of a generation
+	age: u16,
+	lifespan: u16,
+	// The oscillator input is sin(2π phase), the phase advances by 1 / period
+	// every iteration and the OscillatorPeriod output stretches or shrinks the period
+	oscillator_phase: f32,
+	oscillator_period: f32,
+	// Spent moving and gained eating food, in [0, 1]
+	energy: f32,
+	// Dead creatures are out of the world and can't be selected
+	alive: bool,
+	// Draws the random inputs. Every creature has its own generator, seeded at
+	// spawn, so that creatures can sense in parallel and still be deterministic.
+	sensor_rng: ChaCha8Rng,
 }
 
+// Bounds of the oscillator period, in iterations
+const MIN_OSCILLATOR_PERIOD: f32 = 2f32;
+const MAX_OSCILLATOR_PERIOD: f32 = 1024f32;
+
 impl Display for Creature {
 	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
 		write!(
 			f,
 			"{}: {:?}: {:?}",
-			self.genes
-				.iter()
-				.map(|gene| format!("{}", gene))
-				.collect::<Vec<String>>()
-				.join(" "),
-			self.position,
-			self.direction,
+			self.genome, self.position, self.direction,
 		)
 	}
 }
 
 impl Creature {
-	pub fn init_random(
-		num_internal_neurons: u8,
-		world: &mut world::World,
-		gene_pool: &Vec<Vec<gene::Gene>>,
+	// A newborn creature in a free position of the world, the caller places it there
+	pub fn init_random<R: Rng + ?Sized>(
+		config: &config::SimulationConfig,
+		world: &world::World,
+		gene_pool: &[genome::Genome],
+		stats: &mut mutation::MutationStats,
+		rng: &mut R,
 	) -> Creature {
-		let mut rng = rand::thread_rng();
-		let r: u16 = rng.gen();
-
-		// Get a random set of genes from the gene pool
-		let mut genes: Vec<gene::Gene> = Vec::new();
-		for gene in gene_pool
-			.get((r % gene_pool.len() as u16) as usize)
-			.unwrap()
-		{
-			let mc: f32 = rng.gen();
-			if mc < MUTATION_CHANCE {
-				let mutation: u8 = rng.gen();
-				let mut new_gene = gene.clone();
-				new_gene.mutate(mutation % 32);
-				genes.push(new_gene);
-			} else {
-				genes.push(gene.clone());
-			}
-		}
+		// Get the genes of one parent from the gene pool, or cross over two parents
+		let mut genome = config.reproduction.offspring(gene_pool, rng);
+		// With adaptive mutation the genome decides the chance of bit flips
+		let bit_flip_rate =
+			config
+				.adaptive_mutation
+				.mutate_rate(&mut genome, config.mutation_chance, rng);
+		config
+			.point_mutation
+			.mutate(&mut genome.genes, bit_flip_rate, stats, rng);
+		// Insertions, deletions, duplications and swaps change the length of the genome
+		config
+			.structural_mutation
+			.mutate(&mut genome.genes, stats, rng);
 
-		let mut rng = rand::thread_rng();
 		let mut position: world::Position;
 		loop {
 			let rx: u16 = rng.gen();
@@ -66,21 +80,25 @@ impl Creature {
 				x: rx % world.boundary.width,
 				y: ry % world.boundary.height,
 			};
-			if !world.coordinates.contains_key(&position) {
+			if !world.is_blocked(&position) {
 				break;
 			}
 		}
 
-		let creature = Creature {
-			brain: brain::Brain::init(num_internal_neurons),
-			genes,
+		Creature {
+			brain: brain::Brain::init(config.num_internal_neurons),
+			genome,
 			position,
-			direction: rand::random(),
-		};
-		world
-			.coordinates
-			.insert(creature.position, creature.clone());
-		creature
+			direction: rng.gen(),
+			last_movement: world::DeltaPosition { x: 0f32, y: 0f32 },
+			age: 0,
+			lifespan: config.num_iterations,
+			oscillator_phase: 0f32,
+			oscillator_period: config.oscillator_period as f32,
+			energy: config.energy.initial,
+			alive: true,
+			sensor_rng: ChaCha8Rng::seed_from_u64(rng.gen()),
+		}
 	}
 
 	pub fn init(num_internal_neurons: u8, num_genes: u8) -> Creature {
@@ -97,22 +115,190 @@ impl Creature {
 
 		Creature {
 			brain: brain::Brain::init(num_internal_neurons),
-			genes,
+			genome: genome::Genome {
+				genes,
+				mutation_rate: None,
+			},
 			position: world::Position { x: 0, y: 0 },
 			direction: world::Direction::North,
+			last_movement: world::DeltaPosition { x: 0f32, y: 0f32 },
+			age: 0,
+			lifespan: config::SimulationConfig::default().num_iterations,
+			oscillator_phase: 0f32,
+			oscillator_period: config::SimulationConfig::default().oscillator_period as f32,
+			energy: config::SimulationConfig::default().energy.initial,
+			alive: true,
+			sensor_rng: ChaCha8Rng::seed_from_u64(0),
 		}
 	}
 
+	// Only reads the world, see sensor_rng
 	pub fn set_inputs(&mut self, world: &world::World) {
-		self.brain
-			.set_inputs(world, &self.position, &self.direction);
+		let context = brain::SensorContext {
+			position: self.position,
+			direction: self.direction,
+			last_movement: self.last_movement,
+			neighbourhood: if self.brain.senses_population(&self.genome.genes) {
+				world.neighbourhood(&self.position, world.sensor_radius)
+			} else {
+				world::Neighbourhood::empty(world.sensor_radius)
+			},
+			age: (self.age as f32 / self.lifespan.max(1) as f32).min(1f32),
+			oscillator: (2f32 * std::f32::consts::PI * self.oscillator_phase).sin(),
+			energy: self.energy,
+		};
+		self.brain.set_inputs(world, &context, &mut self.sensor_rng);
 	}
 
+	// Thinks, turns and gets older, the move in the new heading happens in the world
 	pub fn compute_next_state(&mut self) {
-		self.brain.compute_neurons_state(&self.genes);
+		self.brain.compute_neurons_state(&self.genome.genes);
+		self.direction = self.brain.turn(&self.direction);
+		// with the output at 1 (or -1) the period doubles (or halves) in 8 iterations
+		self.oscillator_period = (self.oscillator_period
+			* 2f32.powf(self.brain.oscillator_period_change() / 8f32))
+		.clamp(MIN_OSCILLATOR_PERIOD, MAX_OSCILLATOR_PERIOD);
+		self.oscillator_phase = (self.oscillator_phase + 1f32 / self.oscillator_period).fract();
+		self.age = self.age.saturating_add(1);
+	}
+
+	pub fn pheromone_emission(&self) -> f32 {
+		self.brain.pheromone_emission()
+	}
+
+	pub fn age(&self) -> u16 {
+		self.age
+	}
+
+	pub fn oscillator_period(&self) -> f32 {
+		self.oscillator_period
+	}
+
+	pub fn energy(&self) -> f32 {
+		self.energy
+	}
+
+	// Called by the world when the creature moves or eats, the energy stays in [0, 1]
+	pub fn add_energy(&mut self, delta: f32) {
+		self.energy = (self.energy + delta).clamp(0f32, 1f32);
 	}
 
-	pub fn desired_move(&self) -> world::DeltaPosition {
-		self.brain.desired_move(&self.direction)
+	pub fn alive(&self) -> bool {
+		self.alive
 	}
+
+	// Called by the world when the creature dies, e.g. on a lethal border
+	pub fn kill(&mut self) {
+		self.alive = false;
+	}
+
+	pub fn direction(&self) -> world::Direction {
+		self.direction
+	}
+
+	// Called by the world when a move succeeds, the heading follows the move.
+	// The movement is given because the position can wrap around the world.
+	pub fn move_to(&mut self, position: world::Position, movement: world::DeltaPosition) {
+		self.last_movement = movement;
+		self.direction = self.direction.follow(&self.last_movement);
+		self.position = position;
+	}
+
+	pub fn desired_move<R: Rng + ?Sized>(&self, rng: &mut R) -> world::DeltaPosition {
+		self.brain.desired_move(&self.direction, rng)
+	}
+}
+
+#[test]
+fn should_spawn_identical_creatures_with_same_seed() {
+	use rand_chacha::ChaCha8Rng;
+
+	fn spawn(seed: u64) -> Vec<String> {
+		let config = config::SimulationConfig::default();
+		let mut rng = ChaCha8Rng::seed_from_u64(seed);
+		let mut world = world::World::init(&config);
+		let gene_pool: Vec<genome::Genome> = (0..10)
+			.map(|_| genome::Genome::init_random(config.num_genes, &mut rng))
+			.collect();
+		(0..20)
+			.map(|id| {
+				let creature = Creature::init_random(
+					&config,
+					&world,
+					&gene_pool,
+					&mut mutation::MutationStats::default(),
+					&mut rng,
+				);
+				world.place_creature(id, &creature.position);
+				format!("{}", creature)
+			})
+			.collect()
+	}
+
+	assert_eq!(spawn(42), spawn(42));
+	assert_ne!(spawn(42), spawn(43));
+}
+
+#[test]
+fn should_spawn_genomes_of_different_lengths() {
+	let config = config::SimulationConfig {
+		structural_mutation: mutation::StructuralMutation {
+			insertion_rate: 0.2f32,
+			deletion_rate: 0.2f32,
+			..mutation::StructuralMutation::default()
+		},
+		..config::SimulationConfig::default()
+	};
+	let mut rng = crate::test_rng();
+	let mut world = world::World::init(&config);
+	let gene_pool = vec![genome::Genome::init_random(config.num_genes, &mut rng)];
+	let mut creatures: Vec<Creature> = (0..50)
+		.map(|id| {
+			let creature = Creature::init_random(
+				&config,
+				&world,
+				&gene_pool,
+				&mut mutation::MutationStats::default(),
+				&mut rng,
+			);
+			world.place_creature(id, &creature.position);
+			creature
+		})
+		.collect();
+	assert!(creatures
+		.iter()
+		.any(|creature| creature.genome.genes.len() != gene_pool[0].genes.len()));
+	// brains work with any number of genes
+	for creature in creatures.iter_mut() {
+		creature.set_inputs(&world);
+		creature.compute_next_state();
+	}
+}
+
+#[test]
+fn should_age_and_oscillate() {
+	let world = world::World::init(&config::SimulationConfig::default());
+	let mut creature = Creature::init(1, 0);
+	assert_eq!(creature.oscillator_period(), 20f32);
+	for _ in 0..5 {
+		creature.set_inputs(&world);
+		creature.compute_next_state();
+	}
+	assert_eq!(creature.age(), 5);
+	// without connections the period doesn't change, a quarter of it has passed
+	assert_eq!(creature.oscillator_period(), 20f32);
+	let oscillator = (2f32 * std::f32::consts::PI * creature.oscillator_phase).sin();
+	assert_gt!(oscillator, 0.99f32);
+
+	// Age, positive after the first iteration, drives OscillatorPeriod and stretches the period
+	creature.genome.genes = vec![gene::Gene::init(
+		brain::NeuronLayer::Input,
+		brain::input_number(brain::NeuronType::Age),
+		brain::NeuronLayer::Output,
+		brain::output_number(brain::NeuronType::OscillatorPeriod),
+		32767,
+	)];
+	creature.set_inputs(&world);
+	creature.compute_next_state();
+	assert_gt!(creature.oscillator_period(), 20f32);
 }
@@ -0,0 +1,410 @@
+use super::gene::{Gene, WEIGHT_SCALE};
+use super::genome::Genome;
+use rand::prelude::*;
+use rand_distr::{Normal, StandardNormal};
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display, Formatter};
+
+// Mutations of the single genes, each channel has its own chance per gene.
+// The raw bit flip channel is the mutation_chance of the config.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PointMutation {
+	// The weight is changed by a normally distributed amount
+	pub weight_rate: f32,
+	// Standard deviation of the weight change, in the units the brain uses
+	// (a gene weight of 8192 is 1)
+	pub weight_sigma: f32,
+	// The source or the destination neuron is replaced by a random one
+	pub rewire_rate: f32,
+}
+
+impl Default for PointMutation {
+	fn default() -> PointMutation {
+		PointMutation {
+			weight_rate: 0f32,
+			weight_sigma: 0.1f32,
+			rewire_rate: 0f32,
+		}
+	}
+}
+
+// Self-adaptive mutation rate, as in evolution strategies: every genome carries
+// the chance of bit flips of its children, which is inherited and mutated itself.
+// Genomes without a rate start from the mutation_chance of the config.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdaptiveMutation {
+	pub enabled: bool,
+	// The rate of a child is the rate of its parent times exp(tau * N(0, 1))
+	pub tau: f32,
+	pub min_rate: f32,
+	pub max_rate: f32,
+}
+
+impl Default for AdaptiveMutation {
+	fn default() -> AdaptiveMutation {
+		AdaptiveMutation {
+			enabled: false,
+			tau: 0.2f32,
+			min_rate: 0.0001f32,
+			max_rate: 0.5f32,
+		}
+	}
+}
+
+impl AdaptiveMutation {
+	pub fn validate(&self) -> Result<(), String> {
+		if !(self.tau.is_finite() && self.tau >= 0f32) {
+			return Err("adaptive mutation tau can't be negative".to_string());
+		}
+		if !(0f32..=1f32).contains(&self.min_rate) || !(0f32..=1f32).contains(&self.max_rate) {
+			return Err("adaptive mutation rates must be between 0 and 1".to_string());
+		}
+		if self.min_rate > self.max_rate {
+			return Err("adaptive mutation min_rate can't be greater than max_rate".to_string());
+		}
+		Ok(())
+	}
+
+	// Mutates the rate of the genome and returns it. Without adaptive mutation
+	// the genome doesn't carry a rate and the fixed one is returned.
+	pub fn mutate_rate<R: Rng + ?Sized>(
+		&self,
+		genome: &mut Genome,
+		fixed_rate: f32,
+		rng: &mut R,
+	) -> f32 {
+		if !self.enabled {
+			genome.mutation_rate = None;
+			return fixed_rate;
+		}
+		let rate = genome.mutation_rate.unwrap_or(fixed_rate);
+		let factor = (self.tau * rng.sample::<f32, _>(StandardNormal)).exp();
+		let rate = (rate * factor).clamp(self.min_rate, self.max_rate);
+		genome.mutation_rate = Some(rate);
+		rate
+	}
+}
+
+// Mutation rates of the genomes of a generation, only with adaptive mutation
+#[derive(Debug, Clone, PartialEq)]
+pub struct MutationRateStats {
+	pub mean: f32,
+	pub min: f32,
+	pub max: f32,
+}
+
+impl Display for MutationRateStats {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		write!(
+			f,
+			"mean {:.6}, min {:.6}, max {:.6}",
+			self.mean, self.min, self.max
+		)
+	}
+}
+
+impl MutationRateStats {
+	// None when no genome has a mutation rate
+	pub fn init<'a, I: Iterator<Item = &'a Genome>>(genomes: I) -> Option<MutationRateStats> {
+		let rates: Vec<f32> = genomes.filter_map(|genome| genome.mutation_rate).collect();
+		if rates.is_empty() {
+			return None;
+		}
+		Some(MutationRateStats {
+			mean: rates.iter().sum::<f32>() / rates.len() as f32,
+			min: rates.iter().copied().fold(f32::INFINITY, f32::min),
+			max: rates.iter().copied().fold(f32::NEG_INFINITY, f32::max),
+		})
+	}
+}
+
+// Number of mutations of each type that happened while spawning a generation
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MutationStats {
+	pub bit_flips: u32,
+	pub weight_perturbations: u32,
+	pub rewirings: u32,
+	pub insertions: u32,
+	pub deletions: u32,
+	pub duplications: u32,
+	pub swaps: u32,
+}
+
+impl Display for MutationStats {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		write!(
+			f,
+			"{} bit flips, {} weight perturbations, {} rewirings, {} insertions, {} deletions, {} duplications, {} swaps",
+			self.bit_flips,
+			self.weight_perturbations,
+			self.rewirings,
+			self.insertions,
+			self.deletions,
+			self.duplications,
+			self.swaps
+		)
+	}
+}
+
+impl PointMutation {
+	pub fn validate(&self) -> Result<(), String> {
+		for (name, rate) in [
+			("weight_rate", self.weight_rate),
+			("rewire_rate", self.rewire_rate),
+		] {
+			if !(0f32..=1f32).contains(&rate) {
+				return Err(format!("point mutation {} must be between 0 and 1", name));
+			}
+		}
+		if !(self.weight_sigma.is_finite() && self.weight_sigma >= 0f32) {
+			return Err("point mutation weight_sigma can't be negative".to_string());
+		}
+		Ok(())
+	}
+
+	// bit_flip_rate is the mutation_chance of the config
+	pub fn mutate<R: Rng + ?Sized>(
+		&self,
+		genes: &mut [Gene],
+		bit_flip_rate: f32,
+		stats: &mut MutationStats,
+		rng: &mut R,
+	) {
+		// the sigma is validated, so the distribution can always be built
+		let weight_change = Normal::new(0f32, self.weight_sigma * WEIGHT_SCALE).unwrap();
+		for gene in genes.iter_mut() {
+			// a number is always drawn for the bit flip, as it always was
+			if rng.gen::<f32>() < bit_flip_rate {
+				let mutation: u8 = rng.gen();
+				gene.mutate(mutation % 32);
+				stats.bit_flips += 1;
+			}
+			if happens(self.weight_rate, rng) {
+				let weight = gene.weight as f32 + rng.sample(weight_change).round();
+				// `as` saturates at the bounds of i16
+				gene.weight = weight as i16;
+				stats.weight_perturbations += 1;
+			}
+			if happens(self.rewire_rate, rng) {
+				gene.rewire(rng);
+				stats.rewirings += 1;
+			}
+		}
+	}
+}
+
+// Mutations that change the length or the order of a genome.
+// Every rate is the chance, per gene, that the operator is applied at that gene.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StructuralMutation {
+	// A random gene is inserted after the gene
+	pub insertion_rate: f32,
+	// The gene is removed
+	pub deletion_rate: f32,
+	// A copy of the gene is inserted after it
+	pub duplication_rate: f32,
+	// The gene swaps place with the next one
+	pub swap_rate: f32,
+	// Genomes never shrink below min_genes or grow above max_genes through mutations
+	pub min_genes: u16,
+	pub max_genes: u16,
+}
+
+impl Default for StructuralMutation {
+	fn default() -> StructuralMutation {
+		StructuralMutation {
+			insertion_rate: 0f32,
+			deletion_rate: 0f32,
+			duplication_rate: 0f32,
+			swap_rate: 0f32,
+			min_genes: 1,
+			max_genes: 255,
+		}
+	}
+}
+
+impl StructuralMutation {
+	pub fn validate(&self) -> Result<(), String> {
+		for (name, rate) in [
+			("insertion_rate", self.insertion_rate),
+			("deletion_rate", self.deletion_rate),
+			("duplication_rate", self.duplication_rate),
+			("swap_rate", self.swap_rate),
+		] {
+			if !(0f32..=1f32).contains(&rate) {
+				return Err(format!("mutation {} must be between 0 and 1", name));
+			}
+		}
+		if self.min_genes == 0 {
+			return Err("mutation min_genes must be at least 1".to_string());
+		}
+		if self.min_genes > self.max_genes {
+			return Err("mutation min_genes can't be greater than max_genes".to_string());
+		}
+		Ok(())
+	}
+
+	pub fn mutate<R: Rng + ?Sized>(
+		&self,
+		genes: &mut Vec<Gene>,
+		stats: &mut MutationStats,
+		rng: &mut R,
+	) {
+		let mut index = 0;
+		while index < genes.len() {
+			if happens(self.deletion_rate, rng) && genes.len() > self.min_genes as usize {
+				genes.remove(index);
+				stats.deletions += 1;
+				continue;
+			}
+			if happens(self.duplication_rate, rng) && genes.len() < self.max_genes as usize {
+				genes.insert(index + 1, genes[index]);
+				stats.duplications += 1;
+				// the copy isn't mutated again
+				index += 1;
+			}
+			if happens(self.insertion_rate, rng) && genes.len() < self.max_genes as usize {
+				genes.insert(index + 1, Gene::init_random(rng));
+				stats.insertions += 1;
+				index += 1;
+			}
+			if happens(self.swap_rate, rng) && index + 1 < genes.len() {
+				genes.swap(index, index + 1);
+				stats.swaps += 1;
+			}
+			index += 1;
+		}
+	}
+}
+
+// A number is only drawn for the mutations that are enabled, so runs without
+// them use the random number generator as before
+fn happens<R: Rng + ?Sized>(rate: f32, rng: &mut R) -> bool {
+	rate > 0f32 && rng.gen::<f32>() < rate
+}
+
+#[cfg(test)]
+fn genes(text: &str) -> Vec<Gene> {
+	text.parse::<Genome>().unwrap().genes
+}
+
+#[test]
+fn should_not_mutate_by_default() {
+	let mut rng = crate::test_rng();
+	let original = genes("00000000 11111111 22222222");
+	let mut mutated = original.clone();
+	StructuralMutation::default().mutate(&mut mutated, &mut MutationStats::default(), &mut rng);
+	assert_eq!(mutated, original);
+}
+
+#[test]
+fn should_mutate_point_channels() {
+	let mut rng = crate::test_rng();
+	let original = genes("00001000 00001000 00001000");
+
+	let mut stats = MutationStats::default();
+	let mut perturbed = original.clone();
+	PointMutation {
+		weight_rate: 1f32,
+		..PointMutation::default()
+	}
+	.mutate(&mut perturbed, 0f32, &mut stats, &mut rng);
+	assert_eq!(stats.weight_perturbations, 3);
+	assert_eq!(stats.bit_flips + stats.rewirings, 0);
+	// the connections are kept, the weights only move a bit
+	for (gene, original) in perturbed.iter().zip(original.iter()) {
+		assert_eq!(format!("{}", gene)[..4], format!("{}", original)[..4]);
+		assert_lt!((gene.weight - original.weight).abs(), 8192);
+	}
+
+	let mut stats = MutationStats::default();
+	let mut flipped = original.clone();
+	PointMutation::default().mutate(&mut flipped, 1f32, &mut stats, &mut rng);
+	assert_eq!(stats.bit_flips, 3);
+	assert!(flipped.iter().all(|gene| !original.contains(gene)));
+}
+
+#[test]
+fn should_grow_and_shrink_within_bounds() {
+	let mut rng = crate::test_rng();
+	let mut stats = MutationStats::default();
+	let mut grown = genes("00000000 11111111 22222222");
+	StructuralMutation {
+		duplication_rate: 1f32,
+		insertion_rate: 1f32,
+		max_genes: 8,
+		..StructuralMutation::default()
+	}
+	.mutate(&mut grown, &mut stats, &mut rng);
+	assert_eq!(grown.len(), 8);
+	// the first gene is followed by its copy
+	assert_eq!(grown[0], grown[1]);
+	assert_eq!(stats.duplications + stats.insertions, 5);
+
+	let mut shrunk = genes("00000000 11111111 22222222");
+	StructuralMutation {
+		deletion_rate: 1f32,
+		min_genes: 2,
+		..StructuralMutation::default()
+	}
+	.mutate(&mut shrunk, &mut stats, &mut rng);
+	assert_eq!(shrunk, genes("11111111 22222222"));
+	assert_eq!(stats.deletions, 1);
+}
+
+#[test]
+fn should_swap_genes() {
+	let mut rng = crate::test_rng();
+	let mut swapped = genes("00000000 11111111 22222222");
+	StructuralMutation {
+		swap_rate: 1f32,
+		..StructuralMutation::default()
+	}
+	.mutate(&mut swapped, &mut MutationStats::default(), &mut rng);
+	// the first gene is carried along to the end
+	assert_eq!(swapped, genes("11111111 22222222 00000000"));
+}
+
+#[test]
+fn should_reject_invalid_bounds() {
+	let mutation = StructuralMutation {
+		min_genes: 10,
+		max_genes: 5,
+		..StructuralMutation::default()
+	};
+	assert!(mutation.validate().is_err());
+	let mutation = StructuralMutation {
+		swap_rate: 2f32,
+		..StructuralMutation::default()
+	};
+	assert!(mutation.validate().is_err());
+}
+
+#[test]
+fn should_adapt_mutation_rate() {
+	let mut rng = crate::test_rng();
+	let mut genome: Genome = "00000000".parse().unwrap();
+	let fixed = AdaptiveMutation::default();
+	assert_eq!(fixed.mutate_rate(&mut genome, 0.01f32, &mut rng), 0.01f32);
+	assert_eq!(genome.mutation_rate, None);
+
+	let adaptive = AdaptiveMutation {
+		enabled: true,
+		min_rate: 0.005f32,
+		max_rate: 0.02f32,
+		..AdaptiveMutation::default()
+	};
+	let mut genomes = Vec::new();
+	for _ in 0..20 {
+		let rate = adaptive.mutate_rate(&mut genome, 0.01f32, &mut rng);
+		assert!((0.005f32..=0.02f32).contains(&rate));
+		assert_eq!(genome.mutation_rate, Some(rate));
+		genomes.push(genome.clone());
+	}
+	let stats = MutationRateStats::init(genomes.iter()).unwrap();
+	assert!(stats.min <= stats.mean && stats.mean <= stats.max);
+	assert_eq!(MutationRateStats::init(Vec::<Genome>::new().iter()), None);
+}
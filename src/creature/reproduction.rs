@@ -0,0 +1,292 @@
+use super::gene::Gene;
+use super::genome::Genome;
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+// How the genes of two parents are combined into the genome of the child
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Crossover {
+	// Asexual reproduction, the child copies a single parent
+	#[default]
+	None,
+	// The genes before a random cut come from the first parent, the rest from the second
+	SinglePoint,
+	// The genes between two random cuts come from the second parent
+	TwoPoint,
+	// Every gene comes from either parent with the same chance
+	Uniform,
+}
+
+impl FromStr for Crossover {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Crossover, String> {
+		match s.replace('-', "_").as_str() {
+			"none" => Ok(Crossover::None),
+			"single_point" => Ok(Crossover::SinglePoint),
+			"two_point" => Ok(Crossover::TwoPoint),
+			"uniform" => Ok(Crossover::Uniform),
+			_ => Err(format!(
+				"unknown crossover {:?}, expected none, single_point, two_point or uniform",
+				s
+			)),
+		}
+	}
+}
+
+// How the second parent is picked from the gene pool
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MateSelection {
+	// Any genome of the pool, the first parent included
+	#[default]
+	Random,
+	// Any genome of the pool but the first parent, unless it's the only one
+	Distinct,
+	// The genome most similar to the first parent among mate_candidates random
+	// ones, the first parent excluded unless it's the only one
+	Assortative,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReproductionConfig {
+	pub crossover: Crossover,
+	pub mate_selection: MateSelection,
+	// Only used by assortative mate selection
+	pub mate_candidates: u16,
+}
+
+impl Default for ReproductionConfig {
+	fn default() -> ReproductionConfig {
+		ReproductionConfig {
+			crossover: Crossover::None,
+			mate_selection: MateSelection::Random,
+			mate_candidates: 4,
+		}
+	}
+}
+
+impl ReproductionConfig {
+	pub fn validate(&self) -> Result<(), String> {
+		if self.mate_selection == MateSelection::Assortative && self.mate_candidates == 0 {
+			return Err("mate_candidates must be at least 1".to_string());
+		}
+		Ok(())
+	}
+
+	// Genome of a new creature, before mutations.
+	// With crossover the mutation rate is the average of the parents' ones.
+	// The gene pool can't be empty.
+	pub fn offspring<R: Rng + ?Sized>(&self, gene_pool: &[Genome], rng: &mut R) -> Genome {
+		let index = rng.gen_range(0..gene_pool.len());
+		let first = &gene_pool[index];
+		if self.crossover == Crossover::None {
+			return first.clone();
+		}
+		let second = &gene_pool[self.select_mate(gene_pool, index, rng)];
+		Genome {
+			genes: crossover(self.crossover, &first.genes, &second.genes, rng),
+			mutation_rate: match (first.mutation_rate, second.mutation_rate) {
+				(Some(a), Some(b)) => Some((a + b) / 2f32),
+				(a, b) => a.or(b),
+			},
+		}
+	}
+
+	fn select_mate<R: Rng + ?Sized>(
+		&self,
+		gene_pool: &[Genome],
+		first: usize,
+		rng: &mut R,
+	) -> usize {
+		match self.mate_selection {
+			MateSelection::Random => rng.gen_range(0..gene_pool.len()),
+			MateSelection::Distinct => other_than(gene_pool.len(), first, rng),
+			MateSelection::Assortative => {
+				let mut best = other_than(gene_pool.len(), first, rng);
+				for _ in 1..self.mate_candidates {
+					let candidate = other_than(gene_pool.len(), first, rng);
+					if similarity(&gene_pool[first], &gene_pool[candidate])
+						> similarity(&gene_pool[first], &gene_pool[best])
+					{
+						best = candidate;
+					}
+				}
+				best
+			}
+		}
+	}
+}
+
+// Random index in 0..length that skips `first`, unless it's the only one
+fn other_than<R: Rng + ?Sized>(length: usize, first: usize, rng: &mut R) -> usize {
+	if length == 1 {
+		return first;
+	}
+	let index = rng.gen_range(0..length - 1);
+	if index >= first {
+		index + 1
+	} else {
+		index
+	}
+}
+
+// Number of positions where the two genomes have the same gene
+fn similarity(a: &Genome, b: &Genome) -> usize {
+	a.genes
+		.iter()
+		.zip(b.genes.iter())
+		.filter(|(a, b)| a == b)
+		.count()
+}
+
+// Cuts are only placed where both parents have genes, the genes of the longer
+// parent past the end of the shorter one follow the parent of the last segment
+pub fn crossover<R: Rng + ?Sized>(
+	crossover: Crossover,
+	first: &[Gene],
+	second: &[Gene],
+	rng: &mut R,
+) -> Vec<Gene> {
+	let length = first.len().min(second.len());
+	match crossover {
+		Crossover::None => first.to_vec(),
+		Crossover::SinglePoint => {
+			let cut = rng.gen_range(0..=length);
+			first[..cut]
+				.iter()
+				.chain(second[cut..].iter())
+				.copied()
+				.collect()
+		}
+		Crossover::TwoPoint => {
+			let a = rng.gen_range(0..=length);
+			let b = rng.gen_range(0..=length);
+			let (start, end) = (a.min(b), a.max(b));
+			first[..start]
+				.iter()
+				.chain(second[start..end].iter())
+				.chain(first[end..].iter())
+				.copied()
+				.collect()
+		}
+		Crossover::Uniform => (0..length)
+			.map(|index| {
+				if rng.gen::<bool>() {
+					first[index]
+				} else {
+					second[index]
+				}
+			})
+			.chain(first[length..].iter().copied())
+			.collect(),
+	}
+}
+
+#[cfg(test)]
+fn genes(text: &str) -> Vec<Gene> {
+	text.parse::<Genome>().unwrap().genes
+}
+
+#[test]
+fn should_cross_over_single_point() {
+	let mut rng = crate::test_rng();
+	let first = genes("00000000 00000000 00000000 00000000");
+	let second = genes("FFFFFFFF FFFFFFFF FFFFFFFF FFFFFFFF");
+	for _ in 0..20 {
+		let child = crossover(Crossover::SinglePoint, &first, &second, &mut rng);
+		assert_eq!(child.len(), 4);
+		// a prefix of the first parent followed by a suffix of the second
+		let cut = child.iter().take_while(|gene| **gene == first[0]).count();
+		assert!(child[cut..].iter().all(|gene| *gene == second[0]));
+	}
+}
+
+#[test]
+fn should_cross_over_two_point() {
+	let mut rng = crate::test_rng();
+	let first = genes("00000000 00000000 00000000 00000000 00000000");
+	let second = genes("FFFFFFFF FFFFFFFF FFFFFFFF FFFFFFFF FFFFFFFF");
+	for _ in 0..20 {
+		let child = crossover(Crossover::TwoPoint, &first, &second, &mut rng);
+		assert_eq!(child.len(), 5);
+		// the genes of the second parent are contiguous
+		let changes = child.windows(2).filter(|pair| pair[0] != pair[1]).count();
+		assert_le!(changes, 2);
+	}
+}
+
+#[test]
+fn should_cross_over_uniform() {
+	let mut rng = crate::test_rng();
+	let first = genes("00000000 00000000 00000000");
+	let second = genes("FFFFFFFF FFFFFFFF FFFFFFFF FFFFFFFF");
+	let child = crossover(Crossover::Uniform, &first, &second, &mut rng);
+	assert_eq!(child.len(), 3);
+	assert!(child
+		.iter()
+		.all(|gene| *gene == first[0] || *gene == second[0]));
+}
+
+#[test]
+fn should_select_mate() {
+	let mut rng = crate::test_rng();
+	let gene_pool: Vec<Genome> = vec![
+		"00000000 00000000".parse().unwrap(),
+		"00000000 FFFFFFFF".parse().unwrap(),
+		"FFFFFFFF FFFFFFFF".parse().unwrap(),
+	];
+	let distinct = ReproductionConfig {
+		mate_selection: MateSelection::Distinct,
+		..ReproductionConfig::default()
+	};
+	for _ in 0..20 {
+		assert_ne!(distinct.select_mate(&gene_pool, 1, &mut rng), 1);
+	}
+	assert_eq!(distinct.select_mate(&gene_pool[..1], 0, &mut rng), 0);
+
+	// with enough candidates the most similar other genome is always found
+	let assortative = ReproductionConfig {
+		mate_selection: MateSelection::Assortative,
+		mate_candidates: 100,
+		..ReproductionConfig::default()
+	};
+	assert_eq!(assortative.select_mate(&gene_pool, 2, &mut rng), 1);
+	assert_eq!(assortative.select_mate(&gene_pool[..1], 0, &mut rng), 0);
+}
+
+#[test]
+fn should_copy_parent_without_crossover() {
+	let mut rng = crate::test_rng();
+	let gene_pool: Vec<Genome> = vec!["8001C000 00000000".parse().unwrap()];
+	let offspring = ReproductionConfig::default().offspring(&gene_pool, &mut rng);
+	assert_eq!(offspring, gene_pool[0]);
+}
+
+#[test]
+fn should_average_mutation_rates() {
+	let mut rng = crate::test_rng();
+	let gene_pool: Vec<Genome> = vec![
+		"8001C000 00000000 m=0.1".parse().unwrap(),
+		"00000000 8001C000 m=0.3".parse().unwrap(),
+	];
+	let reproduction = ReproductionConfig {
+		crossover: Crossover::Uniform,
+		mate_selection: MateSelection::Distinct,
+		..ReproductionConfig::default()
+	};
+	let offspring = reproduction.offspring(&gene_pool, &mut rng);
+	assert_eq!(offspring.mutation_rate, Some(0.2f32));
+}
+
+#[test]
+fn should_pick_parent_from_large_gene_pool() {
+	let mut rng = crate::test_rng();
+	// more genomes than a u16 can count
+	let gene_pool: Vec<Genome> = vec!["8001C000".parse().unwrap(); 65536];
+	let offspring = ReproductionConfig::default().offspring(&gene_pool, &mut rng);
+	assert_eq!(offspring, gene_pool[0]);
+}
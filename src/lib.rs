@@ -0,0 +1,21 @@
+#[cfg(test)]
+#[macro_use]
+extern crate more_asserts;
+extern crate image;
+extern crate rand;
+extern crate rand_chacha;
+
+pub mod checkpoint;
+pub mod config;
+pub mod creature;
+pub mod selection;
+pub mod simulation;
+pub mod world;
+
+pub use simulation::Simulation;
+
+// Seeded generator for tests that need a reproducible random sequence
+#[cfg(test)]
+pub(crate) fn test_rng() -> rand_chacha::ChaCha8Rng {
+	rand::SeedableRng::seed_from_u64(0)
+}
@@ -1,110 +1,119 @@
-#[macro_use]
-extern crate more_asserts;
-extern crate image;
-extern crate rand;
+extern crate vita;
 use std::fs;
+use std::path::Path;
+use std::process;
 
-use image::{GenericImage, GenericImageView, ImageBuffer, RgbImage};
-
-mod creature;
-mod world;
-
-const NUM_INTERNAL_NEURONS: u8 = 1;
-const NUM_GENES: u8 = 10;
-const NUM_INITIAL_GENE_SEQUENCES: u8 = 200;
-
-const NUM_CREATURES: u16 = 400;
-const NUM_ITERATIONS: u16 = 1000;
-const NUM_GENERATIONS: u16 = 10000;
-
-const GENERATION_TO_SAVE: u16 = 100;
+use vita::checkpoint::Checkpoint;
+use vita::config::{Arguments, SimulationConfig};
+use vita::creature::genome;
+use vita::Simulation;
 
 fn main() {
-    let mut gene_pool: Vec<Vec<creature::gene::Gene>> = Vec::new();
-
-    // initially the gene pool is initialized randomly
-    for _ in 0..NUM_INITIAL_GENE_SEQUENCES {
-        let mut genes: Vec<creature::gene::Gene> = Vec::new();
-        for _ in 0..NUM_GENES {
-            genes.push(creature::gene::Gene::init_random())
+    let mut simulation = match init_simulation() {
+        Ok(simulation) => simulation,
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(1);
         }
-        gene_pool.push(genes);
-    }
+    };
+    // print the seed so that the run can be reproduced with --seed
+    println!("Seed {:?}", simulation.seed());
 
-    for generation in 0..NUM_GENERATIONS {
+    let config = simulation.config().clone();
+    while simulation.generation() < config.num_generations {
+        let generation = simulation.generation();
         println!("Generation {:?}", generation);
 
-        let mut world = world::World::init();
-        let mut creatures: Vec<creature::Creature> = Vec::new();
-        for _ in 0..NUM_CREATURES {
-            creatures.push(creature::Creature::init_random(
-                NUM_INTERNAL_NEURONS,
-                &mut world,
-                &gene_pool,
-            ));
+        // Optimization: don't save every generation
+        if generation.is_multiple_of(config.generation_to_save) {
+            fs::create_dir_all(format!("./generations/{:04}", generation)).unwrap();
+            // the genomes this generation was spawned from
+            genome::write_gene_pool(
+                Path::new(&format!("generations/{:04}/gene_pool.txt", generation)),
+                simulation.gene_pool(),
+            )
+            .unwrap();
+            while simulation.iteration() < config.num_iterations {
+                simulation
+                    .world()
+                    .render()
+                    .save(format!(
+                        "generations/{:04}/{:04}.png",
+                        generation,
+                        simulation.iteration()
+                    ))
+                    .unwrap();
+                simulation.step_iteration();
+            }
         }
 
-        if generation % GENERATION_TO_SAVE == 0 {
-            fs::create_dir_all(format!("./generations/{:04}", generation));
+        // Select the parents and spawn the next generation from their genomes
+        let report = match simulation.run_generation() {
+            Ok(report) => report,
+            Err(error) => {
+                eprintln!("{}", error);
+                process::exit(1);
+            }
+        };
+        if report.extinct {
+            println!(
+                "Generation {}: all creatures have died (extinction {}), {}",
+                generation,
+                simulation.extinctions(),
+                simulation.config().extinction
+            );
+        }
+        println!("Generation {:?}: {}", generation, report);
+        println!(
+            "Generation {:?} mutations: {}",
+            simulation.generation(),
+            simulation.mutation_stats()
+        );
+        if let Some(mutation_rate) = simulation.mutation_rate_stats() {
+            println!(
+                "Generation {:?} mutation rate: {}",
+                simulation.generation(),
+                mutation_rate
+            );
         }
 
-        for iteration in 0..NUM_ITERATIONS {
-            // println!("Iteration {:?}", iteration);
-
-            // Optimization: don't save every generation
-            if generation % GENERATION_TO_SAVE == 0 {
-                let img = ImageBuffer::from_fn(128, 128, |x, y| {
-                    if world.coordinates.contains_key(&world::Position {
-                        x: x as u16,
-                        y: y as u16,
-                    }) {
-                        image::Luma([0u8])
-                    } else {
-                        image::Luma([255u8])
-                    }
-                });
-                img.save(format!(
-                    "generations/{:04}/{:04}.png",
-                    generation, iteration
-                ))
-                .unwrap();
+        if config.checkpoint_interval > 0
+            && simulation
+                .generation()
+                .is_multiple_of(config.checkpoint_interval)
+        {
+            if let Err(error) = simulation.checkpoint().save(&config.checkpoint_file) {
+                eprintln!("{}", error);
             }
-
-            move_all_creatures(&mut world, &mut creatures);
         }
-        // Kill creatures and extract genes of survivors
-        gene_pool = get_genetic_survivors(&creatures);
     }
 }
 
-fn move_all_creatures(world: &mut world::World, creatures: &mut Vec<creature::Creature>) {
-    for creature in creatures.iter_mut() {
-        creature.set_inputs(&world);
-        creature.compute_next_state();
-    }
-    for creature in creatures.iter_mut() {
-        world.move_creature(creature);
+// Starts a new run from the command line configuration, or continues the one
+// stored in the checkpoint passed with --resume
+fn init_simulation() -> Result<Simulation, Box<dyn std::error::Error>> {
+    let arguments = Arguments::parse(std::env::args().skip(1))?;
+    if let Some(path) = &arguments.resume {
+        let mut checkpoint = Checkpoint::load(path)?;
+        checkpoint.config = arguments.apply(checkpoint.config)?;
+        println!(
+            "Resuming from {} at generation {:?}",
+            path.display(),
+            checkpoint.generation
+        );
+        return Ok(Simulation::resume(checkpoint)?);
     }
-}
 
-fn get_genetic_survivors(creatures: &Vec<creature::Creature>) -> Vec<Vec<creature::gene::Gene>> {
-    let mut gene_pool: Vec<Vec<creature::gene::Gene>> = Vec::new();
-    for creature in creatures.iter() {
-        if is_alive(&creature) {
-            gene_pool.push(creature.genes.clone());
+    let config = match &arguments.config {
+        Some(path) => SimulationConfig::from_file(path)?,
+        None => SimulationConfig::default(),
+    };
+    let config = arguments.apply(config)?;
+    match &config.gene_pool_file {
+        Some(path) => {
+            let gene_pool = genome::read_gene_pool(path)?;
+            Ok(Simulation::init_with_gene_pool(config, gene_pool)?)
         }
+        None => Ok(Simulation::init(config)?),
     }
-    if gene_pool.len() == 0 {
-        println!("All creatures have died");
-        panic!()
-    }
-    gene_pool
-}
-
-fn is_alive(creature: &creature::Creature) -> bool {
-    // only survive staying in the center
-    creature.position.x > 30
-        && creature.position.x < 90
-        && creature.position.y > 30
-        && creature.position.y < 90
 }
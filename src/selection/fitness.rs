@@ -0,0 +1,110 @@
+use super::{Creature, SelectionCriterion, World};
+use serde::{Deserialize, Serialize};
+
+#[cfg(test)]
+use super::{creature_at, test_world};
+
+// How good a creature did during its generation, always in [0, 1] and 0 for dead creatures
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Fitness {
+	// Chance of surviving the selection criterion at the end of the generation
+	#[default]
+	Criterion,
+	// 1 on the target, 0 at the opposite corner of the world
+	DistanceToTarget {
+		x: f32,
+		y: f32,
+	},
+	// Fraction of the iterations spent where the selection criterion is satisfied
+	TimeInZone,
+	// Energy left at the end of the generation
+	Energy,
+}
+
+impl Fitness {
+	// Only TimeInZone needs the creatures to be checked at every iteration
+	pub fn tracks_zone(&self) -> bool {
+		*self == Fitness::TimeInZone
+	}
+
+	pub fn evaluate(
+		&self,
+		creature: &Creature,
+		world: &World,
+		criterion: &dyn SelectionCriterion,
+		iterations_in_zone: u16,
+		num_iterations: u16,
+	) -> f32 {
+		if !creature.alive() {
+			return 0f32;
+		}
+		match self {
+			Fitness::Criterion => criterion.survival_probability(creature, world),
+			Fitness::DistanceToTarget { x, y } => {
+				let dx = creature.position.x as f32 - x;
+				let dy = creature.position.y as f32 - y;
+				let diagonal = (world.boundary.width as f32).hypot(world.boundary.height as f32);
+				(1f32 - (dx * dx + dy * dy).sqrt() / diagonal).clamp(0f32, 1f32)
+			}
+			Fitness::Energy => creature.energy(),
+			Fitness::TimeInZone => {
+				if num_iterations == 0 {
+					0f32
+				} else {
+					iterations_in_zone as f32 / num_iterations as f32
+				}
+			}
+		}
+	}
+}
+
+#[test]
+fn should_evaluate_distance_to_target() {
+	let world = test_world();
+	let criterion = super::SelectionConfig::default().build(&world).unwrap();
+	let fitness = Fitness::DistanceToTarget { x: 0f32, y: 0f32 };
+	assert_eq!(
+		fitness.evaluate(&creature_at(0, 0), &world, &*criterion, 0, 10),
+		1f32
+	);
+	assert_lt!(
+		fitness.evaluate(&creature_at(128, 128), &world, &*criterion, 0, 10),
+		0.01f32
+	);
+	assert_gt!(
+		fitness.evaluate(&creature_at(10, 0), &world, &*criterion, 0, 10),
+		fitness.evaluate(&creature_at(20, 0), &world, &*criterion, 0, 10)
+	);
+}
+
+#[test]
+fn should_evaluate_criterion_and_time_in_zone() {
+	let world = test_world();
+	let criterion = super::SelectionConfig::default().build(&world).unwrap();
+	assert_eq!(
+		Fitness::Criterion.evaluate(&creature_at(60, 60), &world, &*criterion, 0, 10),
+		1f32
+	);
+	assert_eq!(
+		Fitness::Criterion.evaluate(&creature_at(0, 0), &world, &*criterion, 0, 10),
+		0f32
+	);
+	assert_eq!(
+		Fitness::TimeInZone.evaluate(&creature_at(0, 0), &world, &*criterion, 5, 10),
+		0.5f32
+	);
+}
+
+#[test]
+fn should_evaluate_energy() {
+	let world = test_world();
+	let criterion = super::SelectionConfig::default().build(&world).unwrap();
+	let mut creature = creature_at(0, 0);
+	creature.add_energy(-1f32);
+	creature.add_energy(0.75f32);
+	assert_eq!(
+		Fitness::Energy.evaluate(&creature, &world, &*criterion, 0, 10),
+		0.75f32
+	);
+}
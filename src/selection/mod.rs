@@ -0,0 +1,564 @@
+use super::creature::Creature;
+use super::world::{Direction, World};
+use image::GrayImage;
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display, Formatter};
+use std::path::{Path, PathBuf};
+
+pub mod fitness;
+pub mod strategy;
+
+// Natural selection: at the end of a generation only the creatures for which
+// is_alive() is true pass their genome to the next generation.
+pub trait SelectionCriterion {
+	fn is_alive(&self, creature: &Creature, world: &World) -> bool;
+
+	// Chance in [0, 1] that the creature survives.
+	// Criteria with a sharp border don't need to override it.
+	fn survival_probability(&self, creature: &Creature, world: &World) -> f32 {
+		if self.is_alive(creature, world) {
+			1f32
+		} else {
+			0f32
+		}
+	}
+}
+
+// Survive inside the rectangle, bounds included
+pub struct Rectangle {
+	pub x_min: u16,
+	pub y_min: u16,
+	pub x_max: u16,
+	pub y_max: u16,
+}
+
+impl SelectionCriterion for Rectangle {
+	fn is_alive(&self, creature: &Creature, _world: &World) -> bool {
+		(self.x_min..=self.x_max).contains(&creature.position.x)
+			&& (self.y_min..=self.y_max).contains(&creature.position.y)
+	}
+}
+
+// Survive within radius from the center, border included
+pub struct Circle {
+	pub center_x: f32,
+	pub center_y: f32,
+	pub radius: f32,
+}
+
+impl SelectionCriterion for Circle {
+	fn is_alive(&self, creature: &Creature, _world: &World) -> bool {
+		let dx = creature.position.x as f32 - self.center_x;
+		let dy = creature.position.y as f32 - self.center_y;
+		dx * dx + dy * dy <= self.radius * self.radius
+	}
+}
+
+// Survive in one of the four size x size squares at the corners of the world
+pub struct Corners {
+	pub size: u16,
+}
+
+impl SelectionCriterion for Corners {
+	fn is_alive(&self, creature: &Creature, world: &World) -> bool {
+		let near_x = creature.position.x < self.size
+			|| creature.position.x >= world.boundary.width.saturating_sub(self.size);
+		let near_y = creature.position.y < self.size
+			|| creature.position.y >= world.boundary.height.saturating_sub(self.size);
+		near_x && near_y
+	}
+}
+
+// Survive less than width positions away from any border of the world
+pub struct EdgeBands {
+	pub width: u16,
+}
+
+impl SelectionCriterion for EdgeBands {
+	fn is_alive(&self, creature: &Creature, world: &World) -> bool {
+		creature.position.x < self.width
+			|| creature.position.y < self.width
+			|| creature.position.x >= world.boundary.width.saturating_sub(self.width)
+			|| creature.position.y >= world.boundary.height.saturating_sub(self.width)
+	}
+}
+
+// Survive on the `side` of the line passing through `position`, line included.
+// E.g. side North and position 64 keeps all the creatures with y >= 64.
+pub struct HalfPlane {
+	pub side: Direction,
+	pub position: u16,
+}
+
+impl SelectionCriterion for HalfPlane {
+	fn is_alive(&self, creature: &Creature, _world: &World) -> bool {
+		match self.side {
+			Direction::North => creature.position.y >= self.position,
+			Direction::South => creature.position.y <= self.position,
+			Direction::East => creature.position.x >= self.position,
+			Direction::West => creature.position.x <= self.position,
+		}
+	}
+}
+
+// Survive where the wrapped criterion would kill
+pub struct Inverted(pub Box<dyn SelectionCriterion>);
+
+impl SelectionCriterion for Inverted {
+	fn is_alive(&self, creature: &Creature, world: &World) -> bool {
+		!self.0.is_alive(creature, world)
+	}
+
+	fn survival_probability(&self, creature: &Creature, world: &World) -> f32 {
+		1f32 - self.0.survival_probability(creature, world)
+	}
+}
+
+// Survive when the wrapped criterion is satisfied anywhere within margin cells
+// (horizontally and vertically) of the creature. Used to give a population that
+// went extinct a second chance.
+pub struct Relaxed<'a> {
+	pub criterion: &'a dyn SelectionCriterion,
+	pub margin: u16,
+}
+
+impl Relaxed<'_> {
+	// Calls f with the creature moved to every cell within the margin,
+	// across the edges of a toroidal world
+	fn probe<F: FnMut(&Creature) -> bool>(&self, creature: &Creature, world: &World, mut f: F) {
+		let mut probe = creature.clone();
+		for dx in world.offsets(self.margin, world.boundary.width) {
+			for dy in world.offsets(self.margin, world.boundary.height) {
+				let position = match world.offset(&creature.position, dx, dy) {
+					Some(position) => position,
+					None => continue,
+				};
+				probe.position = position;
+				if f(&probe) {
+					return;
+				}
+			}
+		}
+	}
+}
+
+impl SelectionCriterion for Relaxed<'_> {
+	fn is_alive(&self, creature: &Creature, world: &World) -> bool {
+		let mut alive = false;
+		self.probe(creature, world, |probe| {
+			alive = self.criterion.is_alive(probe, world);
+			alive
+		});
+		alive
+	}
+
+	fn survival_probability(&self, creature: &Creature, world: &World) -> f32 {
+		let mut probability = 0f32;
+		self.probe(creature, world, |probe| {
+			probability = probability.max(self.criterion.survival_probability(probe, world));
+			probability >= 1f32
+		});
+		probability
+	}
+}
+
+// Survival is decided by the pixel of a grayscale image under the creature.
+// The image has the size of the world and is aligned with the saved frames.
+// Black survives and white dies, when probability is true the gray levels in
+// between are the chance of survival, otherwise they are rounded to black or white.
+pub struct Mask {
+	pixels: GrayImage,
+	probability: bool,
+}
+
+impl Mask {
+	pub fn load(path: &Path, probability: bool, world: &World) -> Result<Mask, SelectionError> {
+		let pixels = image::open(path)
+			.map_err(|error| SelectionError::Mask(path.to_path_buf(), error.to_string()))?
+			.to_luma8();
+		if pixels.width() != world.boundary.width as u32
+			|| pixels.height() != world.boundary.height as u32
+		{
+			return Err(SelectionError::Mask(
+				path.to_path_buf(),
+				format!(
+					"the mask is {}x{} but the world is {}x{}",
+					pixels.width(),
+					pixels.height(),
+					world.boundary.width,
+					world.boundary.height
+				),
+			));
+		}
+		Ok(Mask {
+			pixels,
+			probability,
+		})
+	}
+
+	// 1 for black, 0 for white
+	fn darkness(&self, creature: &Creature) -> f32 {
+		let pixel = self
+			.pixels
+			.get_pixel(creature.position.x as u32, creature.position.y as u32);
+		1f32 - pixel[0] as f32 / 255f32
+	}
+}
+
+impl SelectionCriterion for Mask {
+	fn is_alive(&self, creature: &Creature, _world: &World) -> bool {
+		self.darkness(creature) >= 0.5f32
+	}
+
+	fn survival_probability(&self, creature: &Creature, world: &World) -> f32 {
+		if self.probability {
+			self.darkness(creature)
+		} else if self.is_alive(creature, world) {
+			1f32
+		} else {
+			0f32
+		}
+	}
+}
+
+#[derive(Debug)]
+pub enum SelectionError {
+	Mask(PathBuf, String),
+}
+
+impl Display for SelectionError {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		match self {
+			SelectionError::Mask(path, error) => {
+				write!(f, "Can't use selection mask {}: {}", path.display(), error)
+			}
+		}
+	}
+}
+
+impl std::error::Error for SelectionError {}
+
+// How the criterion is written in the config file, e.g.
+// [selection]
+// shape = "circle"
+// center_x = 64
+// center_y = 64
+// radius = 30
+// inverted = true
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SelectionConfig {
+	#[serde(flatten)]
+	pub shape: Shape,
+	#[serde(default)]
+	pub inverted: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "shape", rename_all = "snake_case")]
+pub enum Shape {
+	Rectangle {
+		x_min: u16,
+		y_min: u16,
+		x_max: u16,
+		y_max: u16,
+	},
+	Circle {
+		center_x: f32,
+		center_y: f32,
+		radius: f32,
+	},
+	Corners {
+		size: u16,
+	},
+	EdgeBands {
+		width: u16,
+	},
+	HalfPlane {
+		side: Direction,
+		position: u16,
+	},
+	Mask {
+		path: PathBuf,
+		#[serde(default)]
+		probability: bool,
+	},
+}
+
+// Only survive staying in the center, the experiment of the README
+impl Default for SelectionConfig {
+	fn default() -> SelectionConfig {
+		SelectionConfig {
+			shape: Shape::Rectangle {
+				x_min: 31,
+				y_min: 31,
+				x_max: 89,
+				y_max: 89,
+			},
+			inverted: false,
+		}
+	}
+}
+
+impl SelectionConfig {
+	pub fn validate(&self) -> Result<(), String> {
+		match self.shape {
+			Shape::Rectangle {
+				x_min,
+				y_min,
+				x_max,
+				y_max,
+			} => {
+				if x_min > x_max || y_min > y_max {
+					return Err("selection rectangle min can't be greater than max".to_string());
+				}
+			}
+			Shape::Circle { radius, .. } => {
+				if radius.is_nan() || radius < 0f32 {
+					return Err("selection circle radius can't be negative".to_string());
+				}
+			}
+			Shape::Mask { ref path, .. } => {
+				if path.as_os_str().is_empty() {
+					return Err("selection mask path can't be empty".to_string());
+				}
+			}
+			Shape::Corners { .. } | Shape::EdgeBands { .. } | Shape::HalfPlane { .. } => {}
+		}
+		Ok(())
+	}
+
+	// The world is needed to check that a mask has the right size
+	pub fn build(&self, world: &World) -> Result<Box<dyn SelectionCriterion>, SelectionError> {
+		let criterion: Box<dyn SelectionCriterion> = match self.shape {
+			Shape::Rectangle {
+				x_min,
+				y_min,
+				x_max,
+				y_max,
+			} => Box::new(Rectangle {
+				x_min,
+				y_min,
+				x_max,
+				y_max,
+			}),
+			Shape::Circle {
+				center_x,
+				center_y,
+				radius,
+			} => Box::new(Circle {
+				center_x,
+				center_y,
+				radius,
+			}),
+			Shape::Corners { size } => Box::new(Corners { size }),
+			Shape::EdgeBands { width } => Box::new(EdgeBands { width }),
+			Shape::HalfPlane { side, position } => Box::new(HalfPlane { side, position }),
+			Shape::Mask {
+				ref path,
+				probability,
+			} => Box::new(Mask::load(path, probability, world)?),
+		};
+		if self.inverted {
+			Ok(Box::new(Inverted(criterion)))
+		} else {
+			Ok(criterion)
+		}
+	}
+}
+
+#[cfg(test)]
+pub(crate) fn creature_at(x: u16, y: u16) -> Creature {
+	let mut creature = Creature::init(1, 0);
+	creature.position = super::world::Position { x, y };
+	creature
+}
+
+#[cfg(test)]
+pub(crate) fn test_world() -> World {
+	World::init(&super::config::SimulationConfig::default())
+}
+
+#[test]
+fn should_select_rectangle() {
+	let world = test_world();
+	let criterion = SelectionConfig::default().build(&world).unwrap();
+	assert!(!criterion.is_alive(&creature_at(30, 60), &world));
+	assert!(criterion.is_alive(&creature_at(31, 60), &world));
+	assert!(criterion.is_alive(&creature_at(89, 89), &world));
+	assert!(!criterion.is_alive(&creature_at(60, 90), &world));
+	assert!(!criterion.is_alive(&creature_at(0, 0), &world));
+}
+
+#[test]
+fn should_select_circle() {
+	let world = test_world();
+	let criterion = Circle {
+		center_x: 64f32,
+		center_y: 64f32,
+		radius: 10f32,
+	};
+	assert!(criterion.is_alive(&creature_at(64, 64), &world));
+	assert!(criterion.is_alive(&creature_at(74, 64), &world));
+	assert!(!criterion.is_alive(&creature_at(72, 72), &world));
+}
+
+#[test]
+fn should_select_corners() {
+	let world = test_world();
+	let criterion = Corners { size: 10 };
+	assert!(criterion.is_alive(&creature_at(0, 0), &world));
+	assert!(criterion.is_alive(&creature_at(9, 127), &world));
+	assert!(criterion.is_alive(&creature_at(118, 118), &world));
+	assert!(!criterion.is_alive(&creature_at(117, 127), &world));
+	assert!(!criterion.is_alive(&creature_at(64, 0), &world));
+}
+
+#[test]
+fn should_select_edge_bands() {
+	let world = test_world();
+	let criterion = EdgeBands { width: 5 };
+	assert!(criterion.is_alive(&creature_at(4, 64), &world));
+	assert!(criterion.is_alive(&creature_at(64, 123), &world));
+	assert!(!criterion.is_alive(&creature_at(5, 122), &world));
+}
+
+#[test]
+fn should_select_half_plane() {
+	let world = test_world();
+	let north = HalfPlane {
+		side: Direction::North,
+		position: 64,
+	};
+	assert!(north.is_alive(&creature_at(0, 64), &world));
+	assert!(!north.is_alive(&creature_at(0, 63), &world));
+	let west = HalfPlane {
+		side: Direction::West,
+		position: 10,
+	};
+	assert!(west.is_alive(&creature_at(10, 0), &world));
+	assert!(!west.is_alive(&creature_at(11, 0), &world));
+}
+
+#[test]
+fn should_relax() {
+	let world = test_world();
+	let criterion = SelectionConfig::default().build(&world).unwrap();
+	let relaxed = Relaxed {
+		criterion: &*criterion,
+		margin: 5,
+	};
+	assert!(relaxed.is_alive(&creature_at(60, 60), &world));
+	assert!(relaxed.is_alive(&creature_at(26, 94), &world));
+	assert!(!relaxed.is_alive(&creature_at(25, 60), &world));
+	assert_eq!(
+		relaxed.survival_probability(&creature_at(94, 31), &world),
+		1f32
+	);
+	assert_eq!(
+		relaxed.survival_probability(&creature_at(0, 0), &world),
+		0f32
+	);
+
+	// the east edge is 3 cells away from x = 2 across the edge of a toroidal world
+	let east = SelectionConfig {
+		shape: Shape::Rectangle {
+			x_min: 120,
+			y_min: 0,
+			x_max: 127,
+			y_max: 127,
+		},
+		inverted: false,
+	};
+	let criterion = east.build(&world).unwrap();
+	let relaxed = Relaxed {
+		criterion: &*criterion,
+		margin: 5,
+	};
+	assert!(!relaxed.is_alive(&creature_at(2, 60), &world));
+	let toroidal = World::init(&super::config::SimulationConfig {
+		topology: super::world::Topology::Toroidal,
+		..super::config::SimulationConfig::default()
+	});
+	assert!(relaxed.is_alive(&creature_at(2, 60), &toroidal));
+	assert!(!relaxed.is_alive(&creature_at(10, 60), &toroidal));
+}
+
+#[test]
+fn should_invert() {
+	let world = test_world();
+	let criterion = SelectionConfig {
+		inverted: true,
+		..SelectionConfig::default()
+	}
+	.build(&world)
+	.unwrap();
+	assert!(criterion.is_alive(&creature_at(0, 0), &world));
+	assert!(!criterion.is_alive(&creature_at(60, 60), &world));
+}
+
+#[test]
+fn should_parse_config() {
+	let config: SelectionConfig = toml::from_str(
+		"shape = \"half_plane\"\nside = \"east\"\nposition = 100\ninverted = true\n",
+	)
+	.unwrap();
+	assert_eq!(
+		config,
+		SelectionConfig {
+			shape: Shape::HalfPlane {
+				side: Direction::East,
+				position: 100
+			},
+			inverted: true,
+		}
+	);
+	let config: SelectionConfig =
+		serde_json::from_str("{\"shape\": \"corners\", \"size\": 20}").unwrap();
+	assert_eq!(config.shape, Shape::Corners { size: 20 });
+	assert!(!config.inverted);
+}
+
+#[test]
+fn should_select_with_mask() {
+	let world = test_world();
+	let path = std::env::temp_dir().join(format!("vita_mask_{}.png", std::process::id()));
+	// left half black, right half a 25% gray
+	let image = image::ImageBuffer::from_fn(128, 128, |x, _y| {
+		if x < 64 {
+			image::Luma([0u8])
+		} else {
+			image::Luma([191u8])
+		}
+	});
+	image.save(&path).unwrap();
+
+	let mask = Mask::load(&path, false, &world).unwrap();
+	assert!(mask.is_alive(&creature_at(0, 0), &world));
+	assert!(!mask.is_alive(&creature_at(64, 0), &world));
+	assert_eq!(mask.survival_probability(&creature_at(64, 0), &world), 0f32);
+
+	let mask = Mask::load(&path, true, &world).unwrap();
+	assert_eq!(
+		mask.survival_probability(&creature_at(0, 127), &world),
+		1f32
+	);
+	let probability = mask.survival_probability(&creature_at(127, 127), &world);
+	assert_gt!(probability, 0.24f32);
+	assert_lt!(probability, 0.26f32);
+
+	let inverted = Inverted(Box::new(mask));
+	assert_eq!(
+		inverted.survival_probability(&creature_at(0, 127), &world),
+		0f32
+	);
+
+	let small_world = World::init(&super::config::SimulationConfig {
+		world_width: 64,
+		world_height: 64,
+		num_creatures: 10,
+		..super::config::SimulationConfig::default()
+	});
+	assert!(Mask::load(&path, true, &small_world).is_err());
+	std::fs::remove_file(&path).unwrap();
+	assert!(Mask::load(&path, true, &world).is_err());
+}
@@ -0,0 +1,290 @@
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fmt::{self, Display, Formatter};
+
+#[cfg(test)]
+use crate::test_rng;
+
+// How the parents of the next generation are picked from the fitness of the creatures
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SelectionStrategy {
+	// Every creature survives on its own with a chance equal to its fitness,
+	// with the default fitness this is the pass/fail natural selection
+	#[default]
+	Survival,
+	// Only the best fraction of the creatures survives
+	Truncation {
+		fraction: f32,
+	},
+	// Every parent is the best of `size` creatures picked at random
+	Tournament {
+		size: u16,
+	},
+	// Parents are sampled with a chance proportional to the fitness
+	Roulette,
+	// Parents are sampled with a chance proportional to the position in the ranking,
+	// tied creatures get the average of their positions
+	Rank,
+}
+
+// How strong the selection of a generation was
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelectionReport {
+	pub num_selected: usize,
+	pub mean_fitness: f32,
+	pub selected_mean_fitness: f32,
+	// Selection differential (selected_mean_fitness - mean_fitness)
+	// in units of the standard deviation of the fitness
+	pub selection_intensity: f32,
+	// Nobody was selected and the extinction policy was applied,
+	// with the relax policy the rest of the report is for the relaxed selection
+	pub extinct: bool,
+}
+
+impl Display for SelectionReport {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		write!(
+			f,
+			"selected {}, mean fitness {:.3}, selected mean fitness {:.3}, selection intensity {:.3}",
+			self.num_selected,
+			self.mean_fitness,
+			self.selected_mean_fitness,
+			self.selection_intensity
+		)
+	}
+}
+
+impl SelectionReport {
+	pub fn init(fitness: &[f32], selected: &[usize]) -> SelectionReport {
+		let mean_fitness = mean(fitness.iter().copied());
+		let selected_mean_fitness = mean(selected.iter().map(|index| fitness[*index]));
+		let variance = mean(
+			fitness
+				.iter()
+				.map(|f| (f - mean_fitness) * (f - mean_fitness)),
+		);
+		let selection_intensity = if variance > 0f32 && !selected.is_empty() {
+			(selected_mean_fitness - mean_fitness) / variance.sqrt()
+		} else {
+			0f32
+		};
+		SelectionReport {
+			num_selected: selected.len(),
+			mean_fitness,
+			selected_mean_fitness,
+			selection_intensity,
+			extinct: false,
+		}
+	}
+}
+
+fn mean<I: Iterator<Item = f32>>(values: I) -> f32 {
+	let (sum, count) = values.fold((0f32, 0usize), |(sum, count), value| {
+		(sum + value, count + 1)
+	});
+	if count == 0 {
+		0f32
+	} else {
+		sum / count as f32
+	}
+}
+
+impl SelectionStrategy {
+	pub fn validate(&self) -> Result<(), String> {
+		match self {
+			SelectionStrategy::Truncation { fraction } => {
+				if !(*fraction > 0f32 && *fraction <= 1f32) {
+					return Err("truncation fraction must be in (0, 1]".to_string());
+				}
+			}
+			SelectionStrategy::Tournament { size } => {
+				if *size == 0 {
+					return Err("tournament size must be at least 1".to_string());
+				}
+			}
+			SelectionStrategy::Survival | SelectionStrategy::Roulette | SelectionStrategy::Rank => {
+			}
+		}
+		Ok(())
+	}
+
+	// Returns the indexes of the selected creatures, a creature can be selected more than once.
+	// Strategies that sample parents pick as many parents as there are creatures.
+	pub fn select<R: Rng + ?Sized>(&self, fitness: &[f32], rng: &mut R) -> Vec<usize> {
+		match self {
+			SelectionStrategy::Survival => {
+				let mut selected = Vec::new();
+				for (index, f) in fitness.iter().enumerate() {
+					// only draw a number when the outcome is uncertain
+					if *f >= 1f32 || (*f > 0f32 && rng.gen::<f32>() < *f) {
+						selected.push(index);
+					}
+				}
+				selected
+			}
+			SelectionStrategy::Truncation { fraction } => {
+				let mut ranking = ranking(fitness);
+				ranking.reverse();
+				let num_selected = (fitness.len() as f32 * fraction).ceil() as usize;
+				ranking.truncate(num_selected);
+				ranking
+			}
+			SelectionStrategy::Tournament { size } => {
+				if fitness.is_empty() {
+					return Vec::new();
+				}
+				(0..fitness.len())
+					.map(|_| {
+						let mut winner = rng.gen_range(0..fitness.len());
+						for _ in 1..*size {
+							let challenger = rng.gen_range(0..fitness.len());
+							if fitness[challenger] > fitness[winner] {
+								winner = challenger;
+							}
+						}
+						winner
+					})
+					.collect()
+			}
+			SelectionStrategy::Roulette => {
+				let weights: Vec<f32> = fitness.iter().map(|f| f.max(0f32)).collect();
+				sample_proportionally(&weights, fitness.len(), rng)
+			}
+			SelectionStrategy::Rank => {
+				sample_proportionally(&rank_weights(fitness), fitness.len(), rng)
+			}
+		}
+	}
+}
+
+// Indexes of the creatures from the worst to the best, ties keep the creature order
+fn ranking(fitness: &[f32]) -> Vec<usize> {
+	let mut ranking: Vec<usize> = (0..fitness.len()).collect();
+	ranking.sort_by(|a, b| {
+		fitness[*a]
+			.partial_cmp(&fitness[*b])
+			.unwrap_or(Ordering::Equal)
+	});
+	ranking
+}
+
+// The worst creature has weight 1 and the best weight n,
+// tied creatures share the average of the ranks they span
+fn rank_weights(fitness: &[f32]) -> Vec<f32> {
+	let ranking = ranking(fitness);
+	let mut weights = vec![0f32; fitness.len()];
+	let mut start = 0;
+	while start < ranking.len() {
+		let mut end = start + 1;
+		while end < ranking.len() && fitness[ranking[end]] == fitness[ranking[start]] {
+			end += 1;
+		}
+		// ranks start + 1..=end
+		let rank = (start + 1 + end) as f32 / 2f32;
+		for index in &ranking[start..end] {
+			weights[*index] = rank;
+		}
+		start = end;
+	}
+	weights
+}
+
+// Nothing is selected when all the weights are 0
+fn sample_proportionally<R: Rng + ?Sized>(
+	weights: &[f32],
+	num_samples: usize,
+	rng: &mut R,
+) -> Vec<usize> {
+	let mut cumulative: Vec<f32> = Vec::with_capacity(weights.len());
+	let mut total = 0f32;
+	for weight in weights.iter() {
+		total += weight;
+		cumulative.push(total);
+	}
+	if total <= 0f32 {
+		return Vec::new();
+	}
+	(0..num_samples)
+		.map(|_| {
+			let target = rng.gen::<f32>() * total;
+			cumulative
+				.partition_point(|c| *c <= target)
+				.min(weights.len() - 1)
+		})
+		.collect()
+}
+
+#[test]
+fn should_select_survivors() {
+	let selected = SelectionStrategy::Survival.select(&[1f32, 0f32, 1f32, 0f32], &mut test_rng());
+	assert_eq!(selected, vec![0, 2]);
+}
+
+#[test]
+fn should_select_by_truncation() {
+	let fitness = [0.1f32, 0.9f32, 0.5f32, 0.7f32];
+	let selected =
+		SelectionStrategy::Truncation { fraction: 0.5f32 }.select(&fitness, &mut test_rng());
+	assert_eq!(selected, vec![1, 3]);
+}
+
+#[test]
+fn should_select_by_tournament() {
+	let fitness = [0f32, 0f32, 1f32, 0f32];
+	// a tournament with every creature always finds the best one
+	let selected = SelectionStrategy::Tournament { size: 100 }.select(&fitness, &mut test_rng());
+	assert_eq!(selected, vec![2, 2, 2, 2]);
+	let selected = SelectionStrategy::Tournament { size: 1 }.select(&fitness, &mut test_rng());
+	assert_eq!(selected.len(), 4);
+}
+
+#[test]
+fn should_select_by_roulette() {
+	let fitness = [0f32, 0.5f32, 0f32, 0.5f32];
+	let selected = SelectionStrategy::Roulette.select(&fitness, &mut test_rng());
+	assert_eq!(selected.len(), 4);
+	assert!(selected.iter().all(|index| *index == 1 || *index == 3));
+	assert!(SelectionStrategy::Roulette
+		.select(&[0f32, 0f32], &mut test_rng())
+		.is_empty());
+}
+
+#[test]
+fn should_select_by_rank() {
+	// the best creatures come first
+	let fitness: Vec<f32> = (0..1000).map(|index| 1f32 - index as f32 / 1000f32).collect();
+	let selected = SelectionStrategy::Rank.select(&fitness, &mut test_rng());
+	assert_eq!(selected.len(), 1000);
+	let best = selected.iter().filter(|index| **index < 100).count();
+	let worst = selected.iter().filter(|index| **index >= 900).count();
+	assert_gt!(best, worst);
+}
+
+#[test]
+fn should_rank_ties_equally() {
+	assert_eq!(
+		rank_weights(&[0.5f32, 0f32, 0.5f32, 1f32]),
+		vec![2.5f32, 1f32, 2.5f32, 4f32]
+	);
+	// with all equal fitness the pick is uniform
+	let fitness = [0f32; 1000];
+	assert!(rank_weights(&fitness).iter().all(|weight| *weight == 500.5f32));
+	let selected = SelectionStrategy::Rank.select(&fitness, &mut test_rng());
+	let first = selected.iter().filter(|index| **index < 100).count();
+	let last = selected.iter().filter(|index| **index >= 900).count();
+	assert_lt!(first.abs_diff(last), 50);
+}
+
+#[test]
+fn should_report_selection_pressure() {
+	let report = SelectionReport::init(&[0f32, 1f32, 0f32, 1f32], &[1, 3]);
+	assert_eq!(report.num_selected, 2);
+	assert_eq!(report.mean_fitness, 0.5f32);
+	assert_eq!(report.selected_mean_fitness, 1f32);
+	assert_eq!(report.selection_intensity, 1f32);
+
+	let report = SelectionReport::init(&[1f32, 1f32], &[0, 1]);
+	assert_eq!(report.selection_intensity, 0f32);
+}
@@ -0,0 +1,43 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display, Formatter};
+
+// What to do when the selection strategy doesn't select any creature,
+// either because every creature died or, with the survival and roulette
+// strategies, because no living creature was picked
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "policy", rename_all = "snake_case")]
+pub enum ExtinctionPolicy {
+	// End the run with SimulationError::Extinction
+	#[default]
+	Abort,
+	// Spawn the next generation from a new random gene pool
+	Reseed,
+	// Spawn the next generation again from the gene pool of the extinct one
+	Rollback,
+	// Select again with the selection criterion relaxed by margin cells,
+	// doubling the margin until someone is selected or it covers the world.
+	// Only valid with the criterion fitness, the others don't change.
+	Relax {
+		margin: u16,
+	},
+}
+
+impl ExtinctionPolicy {
+	pub fn validate(&self) -> Result<(), String> {
+		if let ExtinctionPolicy::Relax { margin: 0 } = self {
+			return Err("extinction relax margin must be at least 1".to_string());
+		}
+		Ok(())
+	}
+}
+
+impl Display for ExtinctionPolicy {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		match self {
+			ExtinctionPolicy::Abort => write!(f, "abort"),
+			ExtinctionPolicy::Reseed => write!(f, "reseed"),
+			ExtinctionPolicy::Rollback => write!(f, "rollback"),
+			ExtinctionPolicy::Relax { margin } => write!(f, "relax by {}", margin),
+		}
+	}
+}
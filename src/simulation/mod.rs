@@ -0,0 +1,692 @@
+use super::checkpoint::Checkpoint;
+use super::config::{ConfigError, SimulationConfig};
+use super::creature::mutation::{MutationRateStats, MutationStats};
+use super::creature::{genome::Genome, Creature};
+use super::selection::strategy::SelectionReport;
+use super::selection::{Relaxed, SelectionCriterion, SelectionError};
+use super::world::map::{self, MapError};
+use super::world::{Position, World};
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use rayon::prelude::*;
+use std::collections::HashSet;
+use std::fmt::{self, Display, Formatter};
+
+pub mod extinction;
+
+use extinction::ExtinctionPolicy;
+
+#[derive(Debug)]
+pub enum SimulationError {
+	Config(ConfigError),
+	Selection(SelectionError),
+	EmptyGenePool,
+	Map(MapError),
+	// No creature of the generation was selected and the policy is abort
+	Extinction { generation: u16 },
+}
+
+impl Display for SimulationError {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		match self {
+			SimulationError::Config(error) => write!(f, "{}", error),
+			SimulationError::Selection(error) => write!(f, "{}", error),
+			SimulationError::EmptyGenePool => write!(f, "The initial gene pool can't be empty"),
+			SimulationError::Map(error) => write!(f, "{}", error),
+			SimulationError::Extinction { generation } => {
+				write!(f, "All creatures have died at generation {}", generation)
+			}
+		}
+	}
+}
+
+impl std::error::Error for SimulationError {}
+
+impl From<ConfigError> for SimulationError {
+	fn from(error: ConfigError) -> SimulationError {
+		SimulationError::Config(error)
+	}
+}
+
+impl From<SelectionError> for SimulationError {
+	fn from(error: SelectionError) -> SimulationError {
+		SimulationError::Selection(error)
+	}
+}
+
+impl From<MapError> for SimulationError {
+	fn from(error: MapError) -> SimulationError {
+		SimulationError::Map(error)
+	}
+}
+
+// The evolution engine: a population of creatures living in a world for a fixed
+// number of iterations per generation, after which the creatures are scored and
+// the selection strategy decides which genomes make it into the gene pool of the
+// next generation.
+pub struct Simulation {
+	config: SimulationConfig,
+	rng: ChaCha8Rng,
+	// State of the generator right before the current generation was spawned
+	generation_rng: ChaCha8Rng,
+	gene_pool: Vec<Genome>,
+	criterion: Box<dyn SelectionCriterion>,
+	world: World,
+	// Read from the map file once, every generation gets a copy in its world
+	obstacles: HashSet<Position>,
+	creatures: Vec<Creature>,
+	// For every creature, the iterations it spent where the selection criterion is
+	// satisfied. Only tracked when the fitness needs it.
+	iterations_in_zone: Vec<u16>,
+	generation: u16,
+	iteration: u16,
+	// Number of generations in which no creature was selected
+	extinctions: u32,
+	// Mutations that happened while spawning the current generation
+	mutation_stats: MutationStats,
+}
+
+impl Simulation {
+	// Validates the config, creates a random gene pool and spawns the first generation.
+	// When the config has no seed a random one is picked and stored in the config.
+	// The config gene_pool_file is not read here, see init_with_gene_pool.
+	pub fn init(mut config: SimulationConfig) -> Result<Simulation, SimulationError> {
+		let seed = *config.seed.get_or_insert_with(rand::random);
+		let mut rng = ChaCha8Rng::seed_from_u64(seed);
+
+		// initially the gene pool is initialized randomly
+		let gene_pool: Vec<Genome> = (0..config.num_initial_gene_sequences)
+			.map(|_| Genome::init_random(config.num_genes, &mut rng))
+			.collect();
+		Simulation::init_with_rng(config, gene_pool, rng)
+	}
+
+	// Like init, but the first generation is spawned from the given gene pool
+	// (e.g. the survivors of another run) instead of random genomes
+	pub fn init_with_gene_pool(
+		mut config: SimulationConfig,
+		gene_pool: Vec<Genome>,
+	) -> Result<Simulation, SimulationError> {
+		if gene_pool.is_empty() {
+			return Err(SimulationError::EmptyGenePool);
+		}
+		let seed = *config.seed.get_or_insert_with(rand::random);
+		let rng = ChaCha8Rng::seed_from_u64(seed);
+		Simulation::init_with_rng(config, gene_pool, rng)
+	}
+
+	fn init_with_rng(
+		config: SimulationConfig,
+		gene_pool: Vec<Genome>,
+		rng: ChaCha8Rng,
+	) -> Result<Simulation, SimulationError> {
+		config.validate()?;
+		let world = World::init(&config);
+		let mut simulation = Simulation {
+			criterion: config.selection.build(&world)?,
+			world,
+			obstacles: map::load_obstacles(&config)?,
+			config,
+			generation_rng: rng.clone(),
+			rng,
+			gene_pool,
+			creatures: Vec::new(),
+			iterations_in_zone: Vec::new(),
+			generation: 0,
+			iteration: 0,
+			extinctions: 0,
+			mutation_stats: MutationStats::default(),
+		};
+		simulation.spawn_generation();
+		Ok(simulation)
+	}
+
+	// Continues a run from a checkpoint, the generation stored in it is spawned again
+	pub fn resume(checkpoint: Checkpoint) -> Result<Simulation, SimulationError> {
+		checkpoint.config.validate()?;
+		let world = World::init(&checkpoint.config);
+		let mut simulation = Simulation {
+			criterion: checkpoint.config.selection.build(&world)?,
+			world,
+			obstacles: map::load_obstacles(&checkpoint.config)?,
+			config: checkpoint.config,
+			generation_rng: checkpoint.rng.clone(),
+			rng: checkpoint.rng,
+			gene_pool: checkpoint.gene_pool,
+			creatures: Vec::new(),
+			iterations_in_zone: Vec::new(),
+			generation: checkpoint.generation,
+			iteration: 0,
+			extinctions: checkpoint.extinctions,
+			mutation_stats: MutationStats::default(),
+		};
+		simulation.spawn_generation();
+		Ok(simulation)
+	}
+
+	// Snapshot of the run at the beginning of the current generation.
+	// The iterations already run in the current generation are not part of it,
+	// resuming replays them.
+	pub fn checkpoint(&self) -> Checkpoint {
+		Checkpoint {
+			config: self.config.clone(),
+			generation: self.generation,
+			gene_pool: self.gene_pool.clone(),
+			rng: self.generation_rng.clone(),
+			extinctions: self.extinctions,
+		}
+	}
+
+	pub fn config(&self) -> &SimulationConfig {
+		&self.config
+	}
+
+	pub fn seed(&self) -> u64 {
+		self.config.seed.unwrap()
+	}
+
+	pub fn generation(&self) -> u16 {
+		self.generation
+	}
+
+	// Number of iterations already run in the current generation
+	pub fn iteration(&self) -> u16 {
+		self.iteration
+	}
+
+	pub fn extinctions(&self) -> u32 {
+		self.extinctions
+	}
+
+	// Mutations by type that happened while spawning the current generation
+	pub fn mutation_stats(&self) -> &MutationStats {
+		&self.mutation_stats
+	}
+
+	// Mutation rates of the current generation, None without adaptive mutation
+	pub fn mutation_rate_stats(&self) -> Option<MutationRateStats> {
+		MutationRateStats::init(self.creatures.iter().map(|creature| &creature.genome))
+	}
+
+	pub fn world(&self) -> &World {
+		&self.world
+	}
+
+	pub fn population(&self) -> &[Creature] {
+		&self.creatures
+	}
+
+	// The genomes the current generation was spawned from
+	pub fn gene_pool(&self) -> &[Genome] {
+		&self.gene_pool
+	}
+
+	// Every creature senses the world, thinks and then tries to move,
+	// then leaves its pheromone where it ended up. Dead creatures are skipped.
+	// Sensing and thinking only read the world and run in parallel, the moves
+	// are then resolved one creature at a time in the population order.
+	pub fn step_iteration(&mut self) {
+		let world = &self.world;
+		self.creatures
+			.par_iter_mut()
+			.filter(|creature| creature.alive())
+			.for_each(|creature| {
+				creature.set_inputs(world);
+				creature.compute_next_state();
+			});
+		for (id, creature) in self
+			.creatures
+			.iter_mut()
+			.enumerate()
+			.filter(|(_, creature)| creature.alive())
+		{
+			self.world.move_creature(id, creature, &mut self.rng);
+		}
+		for creature in self.creatures.iter().filter(|creature| creature.alive()) {
+			self.world
+				.pheromones
+				.deposit(&creature.position, creature.pheromone_emission());
+		}
+		self.world.pheromones.diffuse_and_evaporate();
+		if self.config.fitness.tracks_zone() {
+			for (creature, iterations) in self
+				.creatures
+				.iter()
+				.zip(self.iterations_in_zone.iter_mut())
+			{
+				if self.criterion.is_alive(creature, &self.world) {
+					*iterations += 1;
+				}
+			}
+		}
+		self.iteration += 1;
+	}
+
+	// Runs the iterations left in the current generation, scores the creatures,
+	// picks the parents with the selection strategy and spawns the next generation
+	// from their genomes. Returns how strong the selection was.
+	// When nobody is selected the extinction policy of the config decides how
+	// the run continues and the report is marked as extinct, with the abort
+	// policy an error is returned.
+	pub fn run_generation(&mut self) -> Result<SelectionReport, SimulationError> {
+		while self.iteration < self.config.num_iterations {
+			self.step_iteration();
+		}
+		let fitness = self.fitness_with(&*self.criterion);
+		let mut selected = self.select_alive(&fitness);
+		let mut report = SelectionReport::init(&fitness, &selected);
+		if selected.is_empty() {
+			self.extinctions += 1;
+			match self.config.extinction {
+				ExtinctionPolicy::Abort => {
+					return Err(SimulationError::Extinction {
+						generation: self.generation,
+					})
+				}
+				ExtinctionPolicy::Reseed => {
+					self.gene_pool = (0..self.config.num_initial_gene_sequences)
+						.map(|_| Genome::init_random(self.config.num_genes, &mut self.rng))
+						.collect();
+				}
+				// the gene pool of the extinct generation is spawned again
+				ExtinctionPolicy::Rollback => {}
+				ExtinctionPolicy::Relax { margin } => {
+					let (relaxed_fitness, relaxed_selected) = self.relaxed_selection(margin);
+					if relaxed_selected.is_empty() {
+						return Err(SimulationError::Extinction {
+							generation: self.generation,
+						});
+					}
+					report = SelectionReport::init(&relaxed_fitness, &relaxed_selected);
+					selected = relaxed_selected;
+				}
+			}
+			report.extinct = true;
+		}
+		if !selected.is_empty() {
+			// A creature selected more than once has more chances to be picked as a parent
+			self.gene_pool = selected
+				.into_iter()
+				.map(|index| self.creatures[index].genome.clone())
+				.collect();
+		}
+
+		self.generation += 1;
+		self.spawn_generation();
+		Ok(report)
+	}
+
+	// Fitness of every creature of the current generation, in the population order
+	pub fn fitness(&self) -> Vec<f32> {
+		self.fitness_with(&*self.criterion)
+	}
+
+	fn fitness_with(&self, criterion: &dyn SelectionCriterion) -> Vec<f32> {
+		self.creatures
+			.iter()
+			.enumerate()
+			.map(|(index, creature)| {
+				self.config.fitness.evaluate(
+					creature,
+					&self.world,
+					criterion,
+					self.iterations_in_zone.get(index).copied().unwrap_or(0),
+					self.iteration,
+				)
+			})
+			.collect()
+	}
+
+	// Runs the selection strategy on the living creatures only, so that the
+	// strategies that never look at a fitness of 0 (e.g. rank) can't pick a dead
+	// one. Returns indexes in the population order, empty when everybody died.
+	fn select_alive(&mut self, fitness: &[f32]) -> Vec<usize> {
+		let alive: Vec<usize> = (0..self.creatures.len())
+			.filter(|index| self.creatures[*index].alive())
+			.collect();
+		let alive_fitness: Vec<f32> = alive.iter().map(|index| fitness[*index]).collect();
+		self.config
+			.selection_strategy
+			.select(&alive_fitness, &mut self.rng)
+			.into_iter()
+			.map(|index| alive[index])
+			.collect()
+	}
+
+	// Selects with the criterion relaxed by margin, doubling it until someone is
+	// selected or the margin covers the whole world.
+	// Returns the relaxed fitness together with the selection.
+	fn relaxed_selection(&mut self, mut margin: u16) -> (Vec<f32>, Vec<usize>) {
+		let max_margin = self.world.boundary.width.max(self.world.boundary.height);
+		loop {
+			let relaxed = Relaxed {
+				criterion: &*self.criterion,
+				margin,
+			};
+			let fitness = self.fitness_with(&relaxed);
+			let selected = self.select_alive(&fitness);
+			if !selected.is_empty() || margin >= max_margin {
+				return (fitness, selected);
+			}
+			margin = margin.saturating_mul(2);
+		}
+	}
+
+	fn spawn_generation(&mut self) {
+		self.generation_rng = self.rng.clone();
+		self.world = World::init(&self.config);
+		self.world.obstacles = self.obstacles.clone();
+		self.world.spawn_food(&self.config.food, &mut self.rng);
+		self.creatures = Vec::new();
+		self.iterations_in_zone = vec![0; self.config.num_creatures as usize];
+		self.iteration = 0;
+		self.mutation_stats = MutationStats::default();
+		for id in 0..self.config.num_creatures as usize {
+			let creature = Creature::init_random(
+				&self.config,
+				&self.world,
+				&self.gene_pool,
+				&mut self.mutation_stats,
+				&mut self.rng,
+			);
+			self.world.place_creature(id, &creature.position);
+			self.creatures.push(creature);
+		}
+	}
+}
+
+#[cfg(test)]
+pub(crate) fn small_config(seed: u64) -> SimulationConfig {
+	SimulationConfig {
+		num_creatures: 400,
+		num_iterations: 50,
+		seed: Some(seed),
+		..SimulationConfig::default()
+	}
+}
+
+#[test]
+fn should_step_iteration() {
+	let mut simulation = Simulation::init(small_config(1)).unwrap();
+	assert_eq!(simulation.population().len(), 400);
+	assert_eq!(simulation.world().num_creatures(), 400);
+	assert_eq!(simulation.iteration(), 0);
+	simulation.step_iteration();
+	simulation.step_iteration();
+	assert_eq!(simulation.iteration(), 2);
+	assert_eq!(simulation.generation(), 0);
+}
+
+#[test]
+fn should_run_generation() {
+	let mut simulation = Simulation::init(small_config(1)).unwrap();
+	assert_eq!(simulation.gene_pool().len(), 200);
+	simulation.step_iteration();
+	let report = simulation.run_generation().unwrap();
+	assert_eq!(simulation.generation(), 1);
+	assert_eq!(simulation.iteration(), 0);
+	assert_eq!(simulation.gene_pool().len(), report.num_selected);
+	// survivors passed the criterion, so they are fitter than the average
+	assert_eq!(report.selected_mean_fitness, 1f32);
+	assert_gt!(report.selection_intensity, 0f32);
+	assert_eq!(simulation.population().len(), 400);
+	// about 40 bit flips are expected with the default mutation chance
+	assert_gt!(simulation.mutation_stats().bit_flips, 0);
+	assert_eq!(simulation.mutation_stats().weight_perturbations, 0);
+}
+
+#[test]
+fn should_spawn_from_gene_pool() {
+	let genome: Genome = "00000000 8001C000".parse().unwrap();
+	let simulation =
+		Simulation::init_with_gene_pool(small_config(1), vec![genome.clone()]).unwrap();
+	assert_eq!(simulation.gene_pool().len(), 1);
+	assert_eq!(simulation.gene_pool()[0], genome);
+	assert!(simulation
+		.population()
+		.iter()
+		.all(|creature| creature.genome.genes.len() == genome.genes.len()));
+
+	assert!(matches!(
+		Simulation::init_with_gene_pool(small_config(1), Vec::new()),
+		Err(SimulationError::EmptyGenePool)
+	));
+}
+
+#[test]
+fn should_reject_invalid_config() {
+	let crowded = SimulationConfig {
+		world_width: 10,
+		world_height: 10,
+		num_creatures: 101,
+		..small_config(1)
+	};
+	assert!(matches!(
+		Simulation::init(crowded),
+		Err(SimulationError::Config(_))
+	));
+
+	let mut checkpoint = Simulation::init(small_config(1)).unwrap().checkpoint();
+	checkpoint.config.extinction = ExtinctionPolicy::Relax { margin: 0 };
+	assert!(matches!(
+		Simulation::resume(checkpoint),
+		Err(SimulationError::Config(_))
+	));
+}
+
+#[test]
+fn should_be_deterministic() {
+	fn run(seed: u64) -> Vec<String> {
+		let mut simulation = Simulation::init(small_config(seed)).unwrap();
+		simulation.run_generation().unwrap();
+		simulation.run_generation().unwrap();
+		simulation
+			.population()
+			.iter()
+			.map(|creature| format!("{}", creature))
+			.collect()
+	}
+	assert_eq!(run(3), run(3));
+	assert_ne!(run(3), run(4));
+}
+
+#[test]
+fn should_not_depend_on_thread_count() {
+	fn run(num_threads: usize) -> Vec<(String, Position)> {
+		let pool = rayon::ThreadPoolBuilder::new()
+			.num_threads(num_threads)
+			.build()
+			.unwrap();
+		pool.install(|| {
+			let mut simulation = Simulation::init(small_config(5)).unwrap();
+			simulation.run_generation().unwrap();
+			for _ in 0..10 {
+				simulation.step_iteration();
+			}
+			simulation
+				.population()
+				.iter()
+				.map(|creature| (format!("{}", creature), creature.position))
+				.collect()
+		})
+	}
+	assert_eq!(run(1), run(4));
+}
+
+#[test]
+fn should_never_select_dead_creatures() {
+	use super::selection::strategy::SelectionStrategy;
+
+	let config = SimulationConfig {
+		selection_strategy: SelectionStrategy::Rank,
+		..small_config(1)
+	};
+	let mut simulation = Simulation::init(config.clone()).unwrap();
+	while simulation.iteration() < config.num_iterations {
+		simulation.step_iteration();
+	}
+	// the genomes of the dead creatures are marked with a mutation rate nobody else has
+	for creature in simulation.creatures.iter_mut().step_by(2) {
+		creature.kill();
+		creature.genome.mutation_rate = Some(0.5f32);
+	}
+	simulation.run_generation().unwrap();
+	assert!(!simulation.gene_pool().is_empty());
+	assert!(simulation
+		.gene_pool()
+		.iter()
+		.all(|genome| genome.mutation_rate.is_none()));
+
+	// nobody is left to select
+	let mut simulation = Simulation::init(config).unwrap();
+	for creature in simulation.creatures.iter_mut() {
+		creature.kill();
+	}
+	assert!(matches!(
+		simulation.run_generation(),
+		Err(SimulationError::Extinction { generation: 0 })
+	));
+}
+
+#[test]
+fn should_select_with_strategy() {
+	use super::selection::fitness::Fitness;
+	use super::selection::strategy::SelectionStrategy;
+	let mut simulation = Simulation::init(SimulationConfig {
+		fitness: Fitness::TimeInZone,
+		selection_strategy: SelectionStrategy::Tournament { size: 3 },
+		..small_config(2)
+	})
+	.unwrap();
+	simulation.run_generation().unwrap();
+	let fitness = simulation.fitness();
+	assert!(fitness.iter().all(|f| (0f32..=1f32).contains(f)));
+	let report = simulation.run_generation().unwrap();
+	// tournaments pick one parent per creature
+	assert_eq!(report.num_selected, 400);
+	assert_eq!(simulation.gene_pool().len(), 400);
+	assert_ge!(report.selected_mean_fitness, report.mean_fitness);
+}
+
+#[test]
+fn should_remove_creatures_killed_by_border() {
+	use super::world::BorderPolicy;
+	let mut simulation = Simulation::init(SimulationConfig {
+		border: BorderPolicy::Lethal,
+		..small_config(3)
+	})
+	.unwrap();
+	while simulation.iteration() < simulation.config().num_iterations {
+		simulation.step_iteration();
+	}
+	let num_alive = simulation
+		.population()
+		.iter()
+		.filter(|creature| creature.alive())
+		.count();
+	assert_lt!(num_alive, 400);
+	assert_eq!(simulation.world().num_creatures(), num_alive);
+	for (creature, fitness) in simulation.population().iter().zip(simulation.fitness()) {
+		if !creature.alive() {
+			assert_eq!(fitness, 0f32);
+		}
+	}
+}
+
+#[test]
+fn should_recover_from_extinction() {
+	use super::selection::{SelectionConfig, Shape};
+	// nobody can survive in a rectangle outside of the world
+	fn config(extinction: ExtinctionPolicy) -> SimulationConfig {
+		SimulationConfig {
+			selection: SelectionConfig {
+				shape: Shape::Rectangle {
+					x_min: 200,
+					y_min: 200,
+					x_max: 300,
+					y_max: 300,
+				},
+				inverted: false,
+			},
+			extinction,
+			..small_config(1)
+		}
+	}
+
+	let mut simulation = Simulation::init(config(ExtinctionPolicy::Abort)).unwrap();
+	assert!(matches!(
+		simulation.run_generation(),
+		Err(SimulationError::Extinction { generation: 0 })
+	));
+	assert_eq!(simulation.extinctions(), 1);
+
+	let mut simulation = Simulation::init(config(ExtinctionPolicy::Rollback)).unwrap();
+	let gene_pool = simulation.gene_pool().to_vec();
+	let report = simulation.run_generation().unwrap();
+	assert!(report.extinct);
+	assert_eq!(report.num_selected, 0);
+	assert_eq!(simulation.generation(), 1);
+	assert_eq!(simulation.gene_pool(), &gene_pool[..]);
+
+	let mut simulation = Simulation::init(config(ExtinctionPolicy::Reseed)).unwrap();
+	simulation.run_generation().unwrap();
+	simulation.run_generation().unwrap();
+	assert_eq!(simulation.extinctions(), 2);
+	assert_eq!(simulation.gene_pool().len(), 200);
+	assert_ne!(simulation.gene_pool(), &gene_pool[..]);
+}
+
+#[test]
+fn should_relax_criterion_on_extinction() {
+	use super::selection::{SelectionConfig, Shape};
+	// a single cell, most generations have nobody on it at the end
+	let mut simulation = Simulation::init(SimulationConfig {
+		selection: SelectionConfig {
+			shape: Shape::Rectangle {
+				x_min: 64,
+				y_min: 64,
+				x_max: 64,
+				y_max: 64,
+			},
+			inverted: false,
+		},
+		num_creatures: 4,
+		extinction: ExtinctionPolicy::Relax { margin: 1 },
+		..small_config(1)
+	})
+	.unwrap();
+	let mut extinct = 0;
+	for _ in 0..3 {
+		let report = simulation.run_generation().unwrap();
+		assert_gt!(report.num_selected, 0);
+		extinct += report.extinct as u32;
+	}
+	assert_eq!(simulation.generation(), 3);
+	assert_gt!(simulation.extinctions(), 0);
+	assert_eq!(simulation.extinctions(), extinct);
+}
+
+#[test]
+fn should_evolve_mutation_rate() {
+	use super::creature::mutation::AdaptiveMutation;
+	let simulation = Simulation::init(small_config(1)).unwrap();
+	assert_eq!(simulation.mutation_rate_stats(), None);
+
+	let mut simulation = Simulation::init(SimulationConfig {
+		adaptive_mutation: AdaptiveMutation {
+			enabled: true,
+			..AdaptiveMutation::default()
+		},
+		..small_config(1)
+	})
+	.unwrap();
+	let first = simulation.mutation_rate_stats().unwrap();
+	assert_lt!(first.min, first.max);
+	simulation.run_generation().unwrap();
+	// the rates are inherited through the gene pool
+	assert!(simulation
+		.gene_pool()
+		.iter()
+		.all(|genome| genome.mutation_rate.is_some()));
+	assert!(simulation.mutation_rate_stats().is_some());
+}
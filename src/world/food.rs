@@ -0,0 +1,161 @@
+use super::{Position, Size};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+#[cfg(test)]
+use crate::test_rng;
+
+// Where the food items of a generation are placed
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "pattern", rename_all = "snake_case")]
+pub enum FoodPattern {
+	// Anywhere in the world with the same chance
+	#[default]
+	Uniform,
+	// Around `clusters` random centers, at most `radius` cells away on each axis
+	Clusters {
+		clusters: u16,
+		radius: u16,
+	},
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FoodConfig {
+	// Food items placed at the start of every generation, 0 disables food
+	pub num_items: u16,
+	pub pattern: FoodPattern,
+	// Energy gained by the creature that reaches an item, the item is then gone
+	pub energy: f32,
+}
+
+impl Default for FoodConfig {
+	fn default() -> FoodConfig {
+		FoodConfig {
+			num_items: 0,
+			pattern: FoodPattern::Uniform,
+			energy: 0.25f32,
+		}
+	}
+}
+
+impl FoodConfig {
+	pub fn validate(&self) -> Result<(), String> {
+		if !(0f32..=1f32).contains(&self.energy) {
+			return Err("food energy must be between 0 and 1".to_string());
+		}
+		if let FoodPattern::Clusters { clusters: 0, .. } = self.pattern {
+			return Err("food clusters must be at least 1".to_string());
+		}
+		Ok(())
+	}
+
+	// The items are distinct, there are fewer than num_items when the world
+	// (or the clusters) can't fit them
+	pub fn spawn<R: Rng + ?Sized>(&self, boundary: &Size, rng: &mut R) -> HashSet<Position> {
+		let mut items = HashSet::new();
+		if self.num_items == 0 {
+			return items;
+		}
+		let centers: Vec<Position> = match self.pattern {
+			FoodPattern::Uniform => Vec::new(),
+			FoodPattern::Clusters { clusters, .. } => (0..clusters)
+				.map(|_| random_position(boundary, rng))
+				.collect(),
+		};
+		// give up on a full world instead of looping forever
+		let max_attempts = self.num_items as u32 * 16;
+		for _ in 0..max_attempts {
+			if items.len() == self.num_items as usize {
+				break;
+			}
+			let position = match self.pattern {
+				FoodPattern::Uniform => random_position(boundary, rng),
+				FoodPattern::Clusters { radius, .. } => {
+					let center = centers[rng.gen_range(0..centers.len())];
+					let offset = |coordinate: u16, size: u16, rng: &mut R| {
+						let min = coordinate.saturating_sub(radius);
+						let max = coordinate.saturating_add(radius).min(size - 1);
+						rng.gen_range(min..=max)
+					};
+					Position {
+						x: offset(center.x, boundary.width, rng),
+						y: offset(center.y, boundary.height, rng),
+					}
+				}
+			};
+			items.insert(position);
+		}
+		items
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EnergyConfig {
+	// Energy of newborn creatures, energy is always between 0 and 1
+	pub initial: f32,
+	// Energy spent for every cell moved, creatures without energy can't move.
+	// 0 (the default) makes moves free.
+	pub move_cost: f32,
+}
+
+impl Default for EnergyConfig {
+	fn default() -> EnergyConfig {
+		EnergyConfig {
+			initial: 0.5f32,
+			move_cost: 0f32,
+		}
+	}
+}
+
+impl EnergyConfig {
+	pub fn validate(&self) -> Result<(), String> {
+		if !(0f32..=1f32).contains(&self.initial) || !(0f32..=1f32).contains(&self.move_cost) {
+			return Err("energy initial and move_cost must be between 0 and 1".to_string());
+		}
+		Ok(())
+	}
+}
+
+fn random_position<R: Rng + ?Sized>(boundary: &Size, rng: &mut R) -> Position {
+	Position {
+		x: rng.gen_range(0..boundary.width),
+		y: rng.gen_range(0..boundary.height),
+	}
+}
+
+#[test]
+fn should_spawn_food() {
+	let boundary = Size {
+		width: 128,
+		height: 128,
+	};
+	assert!(FoodConfig::default()
+		.spawn(&boundary, &mut test_rng())
+		.is_empty());
+
+	let uniform = FoodConfig {
+		num_items: 100,
+		..FoodConfig::default()
+	};
+	let items = uniform.spawn(&boundary, &mut test_rng());
+	assert_eq!(items.len(), 100);
+	assert!(items.iter().all(|item| boundary.inside(item)));
+
+	// a single cluster of radius 2 has only 25 cells
+	let cluster = FoodConfig {
+		num_items: 100,
+		pattern: FoodPattern::Clusters {
+			clusters: 1,
+			radius: 2,
+		},
+		..FoodConfig::default()
+	};
+	let items = cluster.spawn(&boundary, &mut test_rng());
+	assert_le!(items.len(), 25);
+	let min_x = items.iter().map(|item| item.x).min().unwrap();
+	let max_x = items.iter().map(|item| item.x).max().unwrap();
+	assert_le!(max_x - min_x, 4);
+}
@@ -0,0 +1,189 @@
+use super::super::config::SimulationConfig;
+use super::Position;
+use std::collections::HashSet;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub enum MapError {
+	Io(PathBuf, std::io::Error),
+	Invalid(PathBuf, String),
+	// The obstacles leave fewer free cells than there are creatures
+	NoRoom {
+		free_cells: usize,
+		num_creatures: u16,
+	},
+}
+
+impl Display for MapError {
+	fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+		match self {
+			MapError::Io(path, error) => {
+				write!(f, "Can't read map {}: {}", path.display(), error)
+			}
+			MapError::Invalid(path, message) => {
+				write!(f, "Invalid map {}: {}", path.display(), message)
+			}
+			MapError::NoRoom {
+				free_cells,
+				num_creatures,
+			} => write!(
+				f,
+				"The map has {} free cells but there are {} creatures",
+				free_cells, num_creatures
+			),
+		}
+	}
+}
+
+impl std::error::Error for MapError {}
+
+// Obstacle cells of the map_file of the config, none without a map.
+// The map has the size of the world and is aligned with the saved frames:
+// - a PNG image, where dark pixels (below 50% gray) are obstacles
+// - any other file is a text map with a line per row, # for an obstacle and . for a free cell
+pub fn load_obstacles(config: &SimulationConfig) -> Result<HashSet<Position>, MapError> {
+	let path = match &config.map_file {
+		Some(path) => path,
+		None => return Ok(HashSet::new()),
+	};
+	let is_png = path
+		.extension()
+		.is_some_and(|extension| extension.eq_ignore_ascii_case("png"));
+	let rows = if is_png {
+		read_image(path)?
+	} else {
+		let content =
+			fs::read_to_string(path).map_err(|error| MapError::Io(path.clone(), error))?;
+		parse_text(&content).map_err(|message| MapError::Invalid(path.clone(), message))?
+	};
+
+	let width = rows.first().map_or(0, |row| row.len());
+	if rows.len() != config.world_height as usize || width != config.world_width as usize {
+		return Err(MapError::Invalid(
+			path.clone(),
+			format!(
+				"the map is {}x{} but the world is {}x{}",
+				width,
+				rows.len(),
+				config.world_width,
+				config.world_height
+			),
+		));
+	}
+	let obstacles: HashSet<Position> = rows
+		.iter()
+		.enumerate()
+		.flat_map(|(y, row)| {
+			row.iter()
+				.enumerate()
+				.filter(|(_, obstacle)| **obstacle)
+				.map(move |(x, _)| Position {
+					x: x as u16,
+					y: y as u16,
+				})
+		})
+		.collect();
+	let free_cells = width * rows.len() - obstacles.len();
+	if free_cells < config.num_creatures as usize {
+		return Err(MapError::NoRoom {
+			free_cells,
+			num_creatures: config.num_creatures,
+		});
+	}
+	Ok(obstacles)
+}
+
+// Rows of obstacle flags, from y = 0
+fn read_image(path: &Path) -> Result<Vec<Vec<bool>>, MapError> {
+	let pixels = image::open(path)
+		.map_err(|error| MapError::Invalid(path.to_path_buf(), error.to_string()))?
+		.to_luma8();
+	Ok(pixels
+		.rows()
+		.map(|row| row.map(|pixel| pixel[0] < 128).collect())
+		.collect())
+}
+
+// On error returns the message with the line number (starting from 1)
+fn parse_text(content: &str) -> Result<Vec<Vec<bool>>, String> {
+	let rows: Vec<Vec<bool>> = content
+		.lines()
+		.enumerate()
+		.map(|(index, line)| {
+			line.trim_end()
+				.chars()
+				.map(|cell| match cell {
+					'#' => Ok(true),
+					'.' => Ok(false),
+					_ => Err(format!("line {}: unknown cell {:?}", index + 1, cell)),
+				})
+				.collect()
+		})
+		.collect::<Result<_, _>>()?;
+	if let Some(index) = rows.iter().position(|row| row.len() != rows[0].len()) {
+		return Err(format!(
+			"line {}: all the lines must have the same length",
+			index + 1
+		));
+	}
+	Ok(rows)
+}
+
+#[test]
+fn should_load_text_map() {
+	let path = std::env::temp_dir().join(format!("vita_map_{}.txt", std::process::id()));
+	fs::write(&path, "#..\n.#.\n..#\n....\n").unwrap();
+	let mut config = SimulationConfig {
+		world_width: 3,
+		world_height: 3,
+		num_creatures: 6,
+		map_file: Some(path.clone()),
+		..SimulationConfig::default()
+	};
+	assert!(matches!(
+		load_obstacles(&config),
+		Err(MapError::Invalid(_, _))
+	));
+
+	fs::write(&path, "#..\n.#.\n..#\n").unwrap();
+	let obstacles = load_obstacles(&config).unwrap();
+	assert_eq!(obstacles.len(), 3);
+	assert!(obstacles.contains(&Position { x: 0, y: 0 }));
+	assert!(obstacles.contains(&Position { x: 2, y: 2 }));
+
+	config.num_creatures = 7;
+	assert!(matches!(
+		load_obstacles(&config),
+		Err(MapError::NoRoom { free_cells: 6, .. })
+	));
+	fs::remove_file(&path).unwrap();
+
+	assert!(load_obstacles(&SimulationConfig::default())
+		.unwrap()
+		.is_empty());
+	assert!(parse_text("#.x").is_err());
+}
+
+#[test]
+fn should_load_image_map() {
+	let path = std::env::temp_dir().join(format!("vita_map_{}.png", std::process::id()));
+	// a vertical wall at x = 64
+	let image = image::ImageBuffer::from_fn(128, 128, |x, _y| {
+		if x == 64 {
+			image::Luma([0u8])
+		} else {
+			image::Luma([255u8])
+		}
+	});
+	image.save(&path).unwrap();
+	let config = SimulationConfig {
+		map_file: Some(path.clone()),
+		..SimulationConfig::default()
+	};
+	let obstacles = load_obstacles(&config).unwrap();
+	assert_eq!(obstacles.len(), 128);
+	assert!(obstacles.iter().all(|obstacle| obstacle.x == 64));
+	fs::remove_file(&path).unwrap();
+}
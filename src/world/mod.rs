@@ -1,8 +1,16 @@
+use super::config;
 use super::creature;
+use image::{GrayImage, ImageBuffer, Luma};
 use rand::distributions::{Distribution, Standard};
-use rand::thread_rng;
 use rand::Rng;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+pub mod food;
+pub mod map;
+pub mod pheromone;
 
 // The world coordinate system has (0, 0) on bottom left
 //
@@ -21,49 +29,464 @@ use std::collections::HashMap;
 // -|-------------------------------------------> X
 // (0,0)
 
+// Index of a creature in the population of the simulation
+pub type CreatureId = usize;
+
 pub struct World {
-	// Note that the world contains a copy of the creatures, not a reference to them.
-	// The function update_creatures_positions() should be called every time that the position change.
-	pub coordinates: HashMap<Position, creature::Creature>,
+	// The creature in every cell, row by row from y = 0. The world only knows
+	// where the creatures are, the creatures themselves live in the population
+	// of the simulation.
+	grid: Vec<Option<CreatureId>>,
 	pub boundary: Size,
+	pub topology: Topology,
+	// What happens to the creatures moving past the border of a bounded world
+	pub border: BorderPolicy,
+	// Cells no creature can enter, see the map module
+	pub obstacles: HashSet<Position>,
+	// How far creatures sense the population around them
+	pub sensor_radius: u16,
+	// How far creatures see in front of them
+	pub vision_range: u16,
+	// Left by the creatures, diffuses and evaporates every iteration
+	pub pheromones: pheromone::PheromoneField,
+	// Food items not eaten yet, placed with spawn_food()
+	pub food: HashSet<Position>,
+	pub food_energy: f32,
+	// Energy spent by a creature for every cell moved
+	pub move_cost: f32,
+}
+
+// The creatures around a position within a radius, the position itself excluded
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Neighbourhood {
+	pub radius: u16,
+	// Fraction of the cells within the radius that are occupied
+	pub density: f32,
+	// Offset to the nearest creature, None when nobody is within the radius
+	pub nearest: Option<DeltaPosition>,
+}
+
+impl Neighbourhood {
+	pub fn empty(radius: u16) -> Neighbourhood {
+		Neighbourhood {
+			radius,
+			density: 0f32,
+			nearest: None,
+		}
+	}
 }
 
 impl World {
-	pub fn init() -> World {
+	pub fn init(config: &config::SimulationConfig) -> World {
+		let boundary = Size {
+			height: config.world_height,
+			width: config.world_width,
+		};
 		World {
-			coordinates: HashMap::new(),
-			boundary: Size {
-				height: 128,
-				width: 128,
+			grid: vec![None; boundary.width as usize * boundary.height as usize],
+			pheromones: pheromone::PheromoneField::init(
+				&boundary,
+				config.topology,
+				&config.pheromone,
+			),
+			boundary,
+			topology: config.topology,
+			border: config.border,
+			obstacles: HashSet::new(),
+			sensor_radius: config.sensor_radius,
+			vision_range: config.vision_range,
+			food: HashSet::new(),
+			food_energy: config.food.energy,
+			move_cost: config.energy.move_cost,
+		}
+	}
+
+	pub fn spawn_food<R: Rng + ?Sized>(&mut self, config: &food::FoodConfig, rng: &mut R) {
+		self.food = config.spawn(&self.boundary, rng);
+		self.food.retain(|item| !self.obstacles.contains(item));
+	}
+
+	// Distance to the nearest food item within the radius, None when there is none
+	pub fn nearest_food(&self, position: &Position, radius: u16) -> Option<f32> {
+		if self.food.is_empty() {
+			return None;
+		}
+		let mut nearest: Option<u32> = None;
+		for dx in self.offsets(radius, self.boundary.width) {
+			for dy in self.offsets(radius, self.boundary.height) {
+				let squared_distance = (dx * dx + dy * dy) as u32;
+				if squared_distance > radius as u32 * radius as u32
+					|| nearest.is_some_and(|distance| squared_distance >= distance)
+				{
+					continue;
+				}
+				if self
+					.offset(position, dx, dy)
+					.is_some_and(|cell| self.food.contains(&cell))
+				{
+					nearest = Some(squared_distance);
+				}
+			}
+		}
+		nearest.map(|squared_distance| (squared_distance as f32).sqrt())
+	}
+
+	pub fn is_occupied(&self, position: &Position) -> bool {
+		self.creature_at(position).is_some()
+	}
+
+	pub fn creature_at(&self, position: &Position) -> Option<CreatureId> {
+		self.cell(position).and_then(|cell| self.grid[cell])
+	}
+
+	// Puts the creature in the position, the position must be free and inside the world
+	pub fn place_creature(&mut self, id: CreatureId, position: &Position) {
+		if let Some(cell) = self.cell(position) {
+			self.grid[cell] = Some(id);
+		}
+	}
+
+	// Frees the position and returns the creature that was there
+	pub fn remove_creature(&mut self, position: &Position) -> Option<CreatureId> {
+		self.cell(position).and_then(|cell| self.grid[cell].take())
+	}
+
+	pub fn num_creatures(&self) -> usize {
+		self.grid.iter().filter(|cell| cell.is_some()).count()
+	}
+
+	// Index of the position in the grid, None outside the world
+	fn cell(&self, position: &Position) -> Option<usize> {
+		if self.boundary.inside(position) {
+			Some(position.y as usize * self.boundary.width as usize + position.x as usize)
+		} else {
+			None
+		}
+	}
+
+	// Cell at the offset from the position, None when it's outside of a bounded world
+	pub fn offset(&self, position: &Position, dx: i32, dy: i32) -> Option<Position> {
+		self.topology.wrap(
+			position.x as i32 + dx,
+			position.y as i32 + dy,
+			&self.boundary,
+		)
+	}
+
+	// Like Position::move_direction, but following the topology of the world
+	pub fn move_direction(
+		&self,
+		position: &Position,
+		direction: &Direction,
+		step: u16,
+	) -> Option<Position> {
+		let unit = direction.unit();
+		self.offset(
+			position,
+			unit.x as i32 * step as i32,
+			unit.y as i32 * step as i32,
+		)
+	}
+
+	// Offsets along an axis of the given size to look within the radius.
+	// In a toroidal world they are cut so that no cell is seen twice.
+	pub fn offsets(&self, radius: u16, size: u16) -> RangeInclusive<i32> {
+		let radius = radius as i32;
+		match self.topology {
+			Topology::Bounded => -radius..=radius,
+			Topology::Toroidal => -radius.min((size as i32 - 1) / 2)..=radius.min(size as i32 / 2),
+		}
+	}
+
+	// Occupied by a creature or an obstacle
+	pub fn is_blocked(&self, position: &Position) -> bool {
+		self.is_occupied(position) || self.obstacles.contains(position)
+	}
+
+	// Distance to the first creature met walking from the position in the
+	// direction, None when there is none within range or a wall or an obstacle
+	// comes first
+	pub fn look_for_creature(
+		&self,
+		position: &Position,
+		direction: &Direction,
+		range: u16,
+	) -> Option<u16> {
+		// in a toroidal world the walk stops before getting back to the position
+		let length = match direction {
+			Direction::North | Direction::South => self.boundary.height,
+			Direction::East | Direction::West => self.boundary.width,
+		};
+		for step in 1..=range.min(length.saturating_sub(1)) {
+			// the walk stops at the wall
+			let cell = self.move_direction(position, direction, step)?;
+			if self.obstacles.contains(&cell) {
+				return None;
+			}
+			if self.is_occupied(&cell) {
+				return Some(step);
+			}
+		}
+		None
+	}
+
+	// Number of steps from the position in the direction before leaving the world
+	// or hitting an obstacle, None when both are farther than range.
+	// A toroidal world has no border, only obstacles are seen.
+	pub fn look_for_wall(
+		&self,
+		position: &Position,
+		direction: &Direction,
+		range: u16,
+	) -> Option<u16> {
+		for step in 1..=range {
+			match self.move_direction(position, direction, step) {
+				// the border of a bounded world
+				None => return Some(step),
+				Some(cell) if self.obstacles.contains(&cell) => return Some(step),
+				Some(_) => {}
+			}
+		}
+		None
+	}
+
+	// Looks up only the cells within the radius, so the cost depends on the
+	// radius and not on the number of creatures. In a toroidal world the
+	// neighbourhood wraps around the edges.
+	// Ties for the nearest creature go to the lowest x, then the lowest y.
+	pub fn neighbourhood(&self, position: &Position, radius: u16) -> Neighbourhood {
+		let mut num_cells = 0u32;
+		let mut num_creatures = 0u32;
+		let mut nearest: Option<(u32, DeltaPosition)> = None;
+		for dx in self.offsets(radius, self.boundary.width) {
+			for dy in self.offsets(radius, self.boundary.height) {
+				let squared_distance = (dx * dx + dy * dy) as u32;
+				if squared_distance == 0 || squared_distance > radius as u32 * radius as u32 {
+					continue;
+				}
+				let cell = match self.offset(position, dx, dy) {
+					Some(cell) => cell,
+					None => continue,
+				};
+				num_cells += 1;
+				if !self.is_occupied(&cell) {
+					continue;
+				}
+				num_creatures += 1;
+				if nearest.is_none_or(|(distance, _)| squared_distance < distance) {
+					nearest = Some((
+						squared_distance,
+						DeltaPosition {
+							x: dx as f32,
+							y: dy as f32,
+						},
+					));
+				}
+			}
+		}
+		Neighbourhood {
+			radius,
+			density: if num_cells == 0 {
+				0f32
+			} else {
+				num_creatures as f32 / num_cells as f32
 			},
+			nearest: nearest.map(|(_, offset)| offset),
 		}
 	}
 
 	// This function encodes all the complexity of the physics in the world::World.
-	// This function returns the next position that will be assumed by the entity.
-	// The world needs to know already that some entity is in that position, otherwise will panic.
-	// When moving the creatures the world will update in place its knowledge of where the creatures are.
-	pub fn move_creature(&mut self, creature: &mut creature::Creature) {
-		if !self.coordinates.contains_key(&creature.position) {
-			println!("No entity found in world position {:?}. How did the world state got out of sync with creatures?", creature.position);
-			panic!("Position not found");
-		}
-		let delta = creature.desired_move();
-		let next_position = creature.position.move_delta(&delta, 1);
-		if self.coordinates.contains_key(&next_position) {
+	// Creatures killed by a lethal border leave the world.
+	// The creature with the id is moved to the next position it can reach, the
+	// world updates in place its knowledge of where the creatures are.
+	pub fn move_creature<R: Rng + ?Sized>(
+		&mut self,
+		id: CreatureId,
+		creature: &mut creature::Creature,
+		rng: &mut R,
+	) {
+		let delta = creature.desired_move(rng);
+		// Stays zero when the move is blocked
+		creature.last_movement = DeltaPosition { x: 0f32, y: 0f32 };
+		// At most one cell per axis
+		let target = |coordinate: u16, delta: f32| {
+			(coordinate as f32 + delta.clamp(-1f32, 1f32)).floor() as i32
+		};
+		let (x, y) = (
+			target(creature.position.x, delta.x),
+			target(creature.position.y, delta.y),
+		);
+		let (x, y) = if self.topology == Topology::Toroidal
+			|| self.topology.wrap(x, y, &self.boundary).is_some()
+		{
+			(x, y)
+		} else {
+			match self.border {
+				// As in Position::move_delta, a move past the south or west border
+				// stops on it, a move past the other borders is dropped below
+				BorderPolicy::Block => (x.max(0), y.max(0)),
+				BorderPolicy::Reflect => (
+					reflect(x, self.boundary.width),
+					reflect(y, self.boundary.height),
+				),
+				// The creature dies when the target cell, the position plus the delta
+				// clamped to one cell and floored like any other move, is past the border
+				BorderPolicy::Lethal => {
+					self.remove_creature(&creature.position);
+					creature.kill();
+					return;
+				}
+			}
+		};
+		let movement = DeltaPosition {
+			x: (x - creature.position.x as i32) as f32,
+			y: (y - creature.position.y as i32) as f32,
+		};
+		let next_position = match self.topology.wrap(x, y, &self.boundary) {
+			Some(next_position) => next_position,
+			// The move should stay inside the boundary
+			None => return,
+		};
+		if self.is_occupied(&next_position) {
 			// The creature can't move in an already occupied spot
 			return;
 		}
-		if !self.boundary.inside(&next_position) {
-			// The move should stay inside the boundary
+		if self.obstacles.contains(&next_position) {
+			return;
+		}
+		let cost = self.move_cost * movement.length();
+		if cost > 0f32 && creature.energy() <= 0f32 {
+			// Creatures without energy left can't move
 			return;
 		}
 		// Add here any other physical rule that may prevent a creature from moving
 
 		// The move is legal and the creature is updated together with the state of the world
-		self.coordinates.remove(&creature.position);
-		creature.position = next_position;
-		self.coordinates.insert(creature.position, creature.clone());
+		self.remove_creature(&creature.position);
+		self.place_creature(id, &next_position);
+		creature.move_to(next_position, movement);
+		creature.add_energy(-cost);
+		if self.food.remove(&next_position) {
+			creature.add_energy(self.food_energy);
+		}
+	}
+
+	// Draws the world one pixel per position, creatures are black, obstacles dark
+	// grey and food light grey on a white background
+	pub fn render(&self) -> GrayImage {
+		ImageBuffer::from_fn(
+			self.boundary.width as u32,
+			self.boundary.height as u32,
+			|x, y| {
+				if self.is_occupied(&Position {
+					x: x as u16,
+					y: y as u16,
+				}) {
+					Luma([0u8])
+				} else if self.obstacles.contains(&Position {
+					x: x as u16,
+					y: y as u16,
+				}) {
+					Luma([96u8])
+				} else if self.food.contains(&Position {
+					x: x as u16,
+					y: y as u16,
+				}) {
+					Luma([160u8])
+				} else {
+					Luma([255u8])
+				}
+			},
+		)
+	}
+}
+
+// How the edges of the world behave
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Topology {
+	// The border of the world is a wall
+	#[default]
+	Bounded,
+	// Coordinates wrap around, leaving the world on one side enters it from the opposite one
+	Toroidal,
+}
+
+impl FromStr for Topology {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Topology, String> {
+		match s {
+			"bounded" => Ok(Topology::Bounded),
+			"toroidal" => Ok(Topology::Toroidal),
+			_ => Err(format!(
+				"unknown topology {:?}, expected bounded or toroidal",
+				s
+			)),
+		}
+	}
+}
+
+// What happens to a creature moving past the border of a bounded world
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BorderPolicy {
+	// The move is blocked
+	#[default]
+	Block,
+	// The creature bounces back, away from the border
+	Reflect,
+	// The creature dies and leaves the world
+	Lethal,
+}
+
+impl FromStr for BorderPolicy {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<BorderPolicy, String> {
+		match s {
+			"block" => Ok(BorderPolicy::Block),
+			"reflect" => Ok(BorderPolicy::Reflect),
+			"lethal" => Ok(BorderPolicy::Lethal),
+			_ => Err(format!(
+				"unknown border policy {:?}, expected block, reflect or lethal",
+				s
+			)),
+		}
+	}
+}
+
+// Mirrors a coordinate that went past the border back into [0, size)
+fn reflect(coordinate: i32, size: u16) -> i32 {
+	let last = size as i32 - 1;
+	if coordinate < 0 {
+		(-coordinate).min(last)
+	} else if coordinate > last {
+		(2 * last - coordinate).max(0)
+	} else {
+		coordinate
+	}
+}
+
+impl Topology {
+	// Cell at the coordinates, None when they are outside of a bounded world
+	pub fn wrap(&self, x: i32, y: i32, boundary: &Size) -> Option<Position> {
+		match self {
+			Topology::Bounded => {
+				if x < 0 || y < 0 || x >= boundary.width as i32 || y >= boundary.height as i32 {
+					None
+				} else {
+					Some(Position {
+						x: x as u16,
+						y: y as u16,
+					})
+				}
+			}
+			Topology::Toroidal => Some(Position {
+				x: x.rem_euclid(boundary.width as i32) as u16,
+				y: y.rem_euclid(boundary.height as i32) as u16,
+			}),
+		}
 	}
 }
 
@@ -142,13 +565,22 @@ impl Position {
 	}
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct DeltaPosition {
 	pub x: f32,
 	pub y: f32,
 }
 
 impl DeltaPosition {
+	pub fn length(&self) -> f32 {
+		self.x.hypot(self.y)
+	}
+
+	// Scalar product, e.g. how far the delta goes in the direction of another one
+	pub fn dot(&self, other: &DeltaPosition) -> f32 {
+		self.x * other.x + self.y * other.y
+	}
+
 	pub fn move_direction(&self, direction: &Direction, step: f32) -> DeltaPosition {
 		match direction {
 			Direction::North => DeltaPosition {
@@ -171,7 +603,8 @@ impl DeltaPosition {
 	}
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Direction {
 	North,
 	South,
@@ -205,6 +638,42 @@ impl Direction {
 	pub fn rotate_right(&self) -> Direction {
 		self.rotate_left().rotate_left().rotate_left()
 	}
+
+	// Unit vector pointing in the direction
+	pub fn unit(&self) -> DeltaPosition {
+		DeltaPosition { x: 0f32, y: 0f32 }.move_direction(self, 1f32)
+	}
+
+	// Heading after a move by delta. Without movement the heading doesn't change,
+	// after a diagonal move it's kept when it's one of the two components of the
+	// move, otherwise the east-west component is taken.
+	pub fn follow(&self, delta: &DeltaPosition) -> Direction {
+		let horizontal = if delta.x > 0f32 {
+			Some(Direction::East)
+		} else if delta.x < 0f32 {
+			Some(Direction::West)
+		} else {
+			None
+		};
+		let vertical = if delta.y > 0f32 {
+			Some(Direction::North)
+		} else if delta.y < 0f32 {
+			Some(Direction::South)
+		} else {
+			None
+		};
+		match (horizontal, vertical) {
+			(None, None) => *self,
+			(Some(direction), None) | (None, Some(direction)) => direction,
+			(Some(horizontal), Some(vertical)) => {
+				if *self == vertical {
+					vertical
+				} else {
+					horizontal
+				}
+			}
+		}
+	}
 }
 
 #[test]
@@ -286,3 +755,329 @@ fn should_move_position_delta() {
 		Position { x: 1u16, y: 0u16 }
 	);
 }
+
+#[test]
+fn should_index_creatures() {
+	let mut world = World::init(&config::SimulationConfig::default());
+	let position = Position { x: 3, y: 7 };
+	assert_eq!(world.creature_at(&position), None);
+	world.place_creature(42, &position);
+	assert_eq!(world.creature_at(&position), Some(42));
+	assert!(world.is_occupied(&position));
+	assert_eq!(world.num_creatures(), 1);
+	// outside of the world nothing is ever found
+	world.place_creature(1, &Position { x: 128, y: 0 });
+	assert!(!world.is_occupied(&Position { x: 128, y: 0 }));
+	assert_eq!(world.remove_creature(&position), Some(42));
+	assert_eq!(world.remove_creature(&position), None);
+	assert_eq!(world.num_creatures(), 0);
+}
+
+#[test]
+fn should_move_creature_id() {
+	let mut world = World::init(&config::SimulationConfig::default());
+	let mut rng = crate::test_rng();
+	let mut walker = walker_moving_west(Position { x: 10, y: 5 });
+	world.place_creature(7, &walker.position);
+	walker.set_inputs(&world);
+	walker.compute_next_state();
+	world.move_creature(7, &mut walker, &mut rng);
+	assert_eq!(world.creature_at(&Position { x: 9, y: 5 }), Some(7));
+	assert_eq!(world.creature_at(&Position { x: 10, y: 5 }), None);
+}
+
+// WordLocationEastWest drives MoveEastWest: close to the west border the
+// creature keeps moving west
+#[cfg(test)]
+fn walker_moving_west(position: Position) -> creature::Creature {
+	walker_drifting(position, 32767)
+}
+
+// A walker driven by WordLocationEastWest with the given weight, it moves
+// toward the nearest east or west border, by less than a cell for small weights
+#[cfg(test)]
+fn walker_drifting(position: Position, weight: i16) -> creature::Creature {
+	use creature::brain::{input_number, output_number, NeuronLayer, NeuronType};
+	use creature::gene::Gene;
+
+	let mut walker = creature::Creature::init(1, 0);
+	walker.genome.genes = vec![Gene::init(
+		NeuronLayer::Input,
+		input_number(NeuronType::WordLocationEastWest),
+		NeuronLayer::Output,
+		output_number(NeuronType::MoveEastWest),
+		weight,
+	)];
+	walker.position = position;
+	walker
+}
+
+#[test]
+fn should_record_last_movement() {
+	let mut world = World::init(&config::SimulationConfig::default());
+	let mut rng = crate::test_rng();
+	let mut walker = walker_moving_west(Position { x: 10, y: 5 });
+	world.place_creature(0, &walker.position);
+
+	walker.set_inputs(&world);
+	walker.compute_next_state();
+	world.move_creature(0, &mut walker, &mut rng);
+	assert_eq!(walker.position, Position { x: 9, y: 5 });
+	assert_eq!(walker.last_movement, DeltaPosition { x: -1f32, y: 0f32 });
+	// the heading follows the move
+	assert_eq!(walker.direction(), Direction::West);
+
+	// blocked by another creature
+	let mut blocker = creature::Creature::init(1, 0);
+	blocker.position = Position { x: 8, y: 5 };
+	world.place_creature(1, &blocker.position);
+	walker.set_inputs(&world);
+	walker.compute_next_state();
+	world.move_creature(0, &mut walker, &mut rng);
+	assert_eq!(walker.position, Position { x: 9, y: 5 });
+	assert_eq!(walker.last_movement, DeltaPosition { x: 0f32, y: 0f32 });
+}
+
+#[test]
+fn should_eat_food_and_spend_energy() {
+	let mut world = World::init(&config::SimulationConfig::default());
+	world.move_cost = 0.1f32;
+	world.food.insert(Position { x: 8, y: 5 });
+	let mut rng = crate::test_rng();
+	let mut walker = walker_moving_west(Position { x: 10, y: 5 });
+	world.place_creature(0, &walker.position);
+	let mut step = |walker: &mut creature::Creature, world: &mut World| {
+		walker.set_inputs(world);
+		walker.compute_next_state();
+		world.move_creature(0, walker, &mut rng);
+	};
+
+	step(&mut walker, &mut world);
+	assert!((walker.energy() - 0.4f32).abs() < 1e-6);
+	// the food is eaten
+	step(&mut walker, &mut world);
+	assert!((walker.energy() - 0.55f32).abs() < 1e-6);
+	assert!(world.food.is_empty());
+
+	// the last move is made with the energy left, then the creature is stuck
+	world.move_cost = 1f32;
+	step(&mut walker, &mut world);
+	assert_eq!(walker.position, Position { x: 7, y: 5 });
+	assert_eq!(walker.energy(), 0f32);
+	step(&mut walker, &mut world);
+	assert_eq!(walker.position, Position { x: 7, y: 5 });
+}
+
+#[test]
+fn should_follow_movement() {
+	let west = DeltaPosition { x: -1f32, y: 0f32 };
+	let north_east = DeltaPosition { x: 1f32, y: 1f32 };
+	let still = DeltaPosition { x: 0f32, y: 0f32 };
+	assert_eq!(Direction::North.follow(&west), Direction::West);
+	assert_eq!(Direction::South.follow(&still), Direction::South);
+	assert_eq!(Direction::North.follow(&north_east), Direction::North);
+	assert_eq!(Direction::South.follow(&north_east), Direction::East);
+}
+
+#[test]
+fn should_query_neighbourhood() {
+	let mut world = World::init(&config::SimulationConfig::default());
+	let center = Position { x: 0, y: 0 };
+	let empty = world.neighbourhood(&center, 2);
+	assert_eq!(empty.density, 0f32);
+	assert_eq!(empty.nearest, None);
+
+	for position in [
+		Position { x: 2, y: 0 },
+		Position { x: 1, y: 1 },
+		Position { x: 5, y: 5 },
+	] {
+		world.place_creature(1, &position);
+	}
+	// in the corner, the cells within 2 are (1, 0), (2, 0), (0, 1), (1, 1) and (0, 2)
+	let neighbourhood = world.neighbourhood(&center, 2);
+	assert_eq!(neighbourhood.density, 2f32 / 5f32);
+	assert_eq!(
+		neighbourhood.nearest,
+		Some(DeltaPosition { x: 1f32, y: 1f32 })
+	);
+	assert_eq!(
+		world.neighbourhood(&Position { x: 5, y: 5 }, 1).nearest,
+		None
+	);
+}
+
+#[test]
+fn should_look_forward() {
+	let mut world = World::init(&config::SimulationConfig::default());
+	let position = Position { x: 10, y: 120 };
+	world.place_creature(1, &Position { x: 14, y: 120 });
+	assert_eq!(
+		world.look_for_creature(&position, &Direction::East, 10),
+		Some(4)
+	);
+	assert_eq!(
+		world.look_for_creature(&position, &Direction::East, 3),
+		None
+	);
+	assert_eq!(
+		world.look_for_creature(&position, &Direction::West, 10),
+		None
+	);
+
+	// the cell at y = 127 is the last one inside the world
+	assert_eq!(
+		world.look_for_wall(&position, &Direction::North, 10),
+		Some(8)
+	);
+	assert_eq!(world.look_for_wall(&position, &Direction::West, 10), None);
+	assert_eq!(
+		world.look_for_wall(&position, &Direction::West, 11),
+		Some(11)
+	);
+
+	// an obstacle is a wall and hides the creatures behind it
+	world.obstacles.insert(Position { x: 12, y: 120 });
+	assert_eq!(
+		world.look_for_wall(&position, &Direction::East, 10),
+		Some(2)
+	);
+	assert_eq!(
+		world.look_for_creature(&position, &Direction::East, 10),
+		None
+	);
+
+	// World::init doesn't validate the config, an empty world sees nothing
+	let world = World::init(&config::SimulationConfig {
+		world_width: 0,
+		world_height: 0,
+		..config::SimulationConfig::default()
+	});
+	assert_eq!(
+		world.look_for_creature(&Position { x: 0, y: 0 }, &Direction::East, 10),
+		None
+	);
+}
+
+#[test]
+fn should_not_move_into_obstacles() {
+	let mut world = World::init(&config::SimulationConfig::default());
+	world.obstacles.insert(Position { x: 9, y: 5 });
+	let mut rng = crate::test_rng();
+	let mut walker = walker_moving_west(Position { x: 10, y: 5 });
+	world.place_creature(0, &walker.position);
+	walker.set_inputs(&world);
+	walker.compute_next_state();
+	world.move_creature(0, &mut walker, &mut rng);
+	assert_eq!(walker.position, Position { x: 10, y: 5 });
+}
+
+#[test]
+fn should_wrap_around_toroidal_world() {
+	let mut world = World::init(&config::SimulationConfig {
+		topology: Topology::Toroidal,
+		..config::SimulationConfig::default()
+	});
+	let mut rng = crate::test_rng();
+	let mut walker = walker_moving_west(Position { x: 0, y: 5 });
+	world.place_creature(0, &walker.position);
+	walker.set_inputs(&world);
+	walker.compute_next_state();
+	world.move_creature(0, &mut walker, &mut rng);
+	assert_eq!(walker.position, Position { x: 127, y: 5 });
+	assert_eq!(walker.last_movement, DeltaPosition { x: -1f32, y: 0f32 });
+
+	// the creature is seen across the edge
+	let position = Position { x: 1, y: 5 };
+	assert_eq!(
+		world.look_for_creature(&position, &Direction::West, 10),
+		Some(2)
+	);
+	let neighbourhood = world.neighbourhood(&position, 3);
+	assert_eq!(
+		neighbourhood.nearest,
+		Some(DeltaPosition { x: -2f32, y: 0f32 })
+	);
+	// there is no border
+	assert_eq!(world.look_for_wall(&position, &Direction::West, 10), None);
+	// and a creature doesn't see itself
+	assert_eq!(
+		world.look_for_creature(&walker.position, &Direction::East, 1000),
+		None
+	);
+	// the neighbourhood of a small world has each cell once
+	let small = World::init(&config::SimulationConfig {
+		topology: Topology::Toroidal,
+		world_width: 4,
+		world_height: 4,
+		num_creatures: 1,
+		..config::SimulationConfig::default()
+	});
+	assert_eq!(
+		small.offsets(10, 4).count() * small.offsets(10, 4).count(),
+		16
+	);
+}
+
+#[test]
+fn should_apply_border_policy() {
+	let walk = |border: BorderPolicy| {
+		let mut world = World::init(&config::SimulationConfig {
+			border,
+			..config::SimulationConfig::default()
+		});
+		let mut rng = crate::test_rng();
+		let mut walker = walker_moving_west(Position { x: 0, y: 5 });
+		world.place_creature(0, &walker.position);
+		walker.set_inputs(&world);
+		walker.compute_next_state();
+		world.move_creature(0, &mut walker, &mut rng);
+		(walker, world)
+	};
+
+	let (walker, world) = walk(BorderPolicy::Block);
+	assert_eq!(walker.position, Position { x: 0, y: 5 });
+	assert!(walker.alive());
+	assert!(world.is_occupied(&walker.position));
+
+	// bounces back and turns around
+	let (walker, world) = walk(BorderPolicy::Reflect);
+	assert_eq!(walker.position, Position { x: 1, y: 5 });
+	assert_eq!(walker.direction(), Direction::East);
+	assert!(world.is_occupied(&walker.position));
+
+	let (walker, world) = walk(BorderPolicy::Lethal);
+	assert!(!walker.alive());
+	assert_eq!(world.num_creatures(), 0);
+
+	// with the MoveRandom term added, the delta of a walker drifting by a quarter
+	// of a cell reaches a cell past the west or the east border about a quarter
+	// of the times on both sides
+	let mut rng = crate::test_rng();
+	for x in [0, 127] {
+		let mut deaths = 0;
+		for _ in 0..400 {
+			let mut world = World::init(&config::SimulationConfig {
+				border: BorderPolicy::Lethal,
+				..config::SimulationConfig::default()
+			});
+			// tanh(2048 / 8192) is about 0.245
+			let mut walker = walker_drifting(Position { x, y: 5 }, 2048);
+			world.place_creature(0, &walker.position);
+			walker.set_inputs(&world);
+			walker.compute_next_state();
+			world.move_creature(0, &mut walker, &mut rng);
+			if !walker.alive() {
+				deaths += 1;
+			} else {
+				assert_eq!(walker.position.x, x);
+			}
+		}
+		assert_gt!(deaths, 60);
+		assert_lt!(deaths, 140);
+	}
+
+	assert_eq!(reflect(-1, 128), 1);
+	assert_eq!(reflect(128, 128), 126);
+	assert_eq!(reflect(-1, 1), 0);
+}
@@ -0,0 +1,208 @@
+use super::{Position, Size, Topology};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PheromoneConfig {
+	// Pheromone left by a creature whose EmitPheromone output is at 1
+	pub deposit: f32,
+	// Fraction of the pheromone of a cell spread to its neighbours every iteration
+	pub diffusion: f32,
+	// Fraction of the pheromone lost every iteration
+	pub evaporation: f32,
+}
+
+impl Default for PheromoneConfig {
+	fn default() -> PheromoneConfig {
+		PheromoneConfig {
+			deposit: 0.5f32,
+			diffusion: 0.1f32,
+			evaporation: 0.05f32,
+		}
+	}
+}
+
+impl PheromoneConfig {
+	pub fn validate(&self) -> Result<(), String> {
+		for (name, value) in [
+			("deposit", self.deposit),
+			("diffusion", self.diffusion),
+			("evaporation", self.evaporation),
+		] {
+			if !(0f32..=1f32).contains(&value) {
+				return Err(format!("pheromone {} must be between 0 and 1", name));
+			}
+		}
+		Ok(())
+	}
+}
+
+// Amount of pheromone on every cell of the world, always in [0, 1]
+#[derive(Debug, Clone, PartialEq)]
+pub struct PheromoneField {
+	width: u16,
+	height: u16,
+	topology: Topology,
+	values: Vec<f32>,
+	config: PheromoneConfig,
+}
+
+impl PheromoneField {
+	pub fn init(boundary: &Size, topology: Topology, config: &PheromoneConfig) -> PheromoneField {
+		PheromoneField {
+			width: boundary.width,
+			height: boundary.height,
+			topology,
+			values: vec![0f32; boundary.width as usize * boundary.height as usize],
+			config: config.clone(),
+		}
+	}
+
+	// 0 outside the world
+	pub fn get(&self, position: &Position) -> f32 {
+		match self.index(position) {
+			Some(index) => self.values[index],
+			None => 0f32,
+		}
+	}
+
+	// Adds the deposit scaled by the amount, the cell saturates at 1
+	pub fn deposit(&mut self, position: &Position, amount: f32) {
+		if let Some(index) = self.index(position) {
+			let value = &mut self.values[index];
+			*value = (*value + amount.clamp(0f32, 1f32) * self.config.deposit).min(1f32);
+		}
+	}
+
+	// Every cell spreads a fraction of its pheromone evenly to the neighbours
+	// inside the world (across the edges of a toroidal world), then the whole
+	// field evaporates
+	pub fn diffuse_and_evaporate(&mut self) {
+		if self.values.iter().all(|value| *value == 0f32) {
+			return;
+		}
+		let mut next = self.values.clone();
+		for y in 0..self.height {
+			for x in 0..self.width {
+				let value = self.values[self.index_unchecked(x, y)];
+				if value == 0f32 {
+					continue;
+				}
+				let boundary = Size {
+					width: self.width,
+					height: self.height,
+				};
+				let neighbours = [(-1, 0), (1, 0), (0, -1), (0, 1)].map(|(dx, dy)| {
+					self.topology
+						.wrap(x as i32 + dx, y as i32 + dy, &boundary)
+						.map(|neighbour| self.index_unchecked(neighbour.x, neighbour.y))
+				});
+				let num_neighbours = neighbours.iter().flatten().count();
+				if num_neighbours == 0 {
+					continue;
+				}
+				let spread = value * self.config.diffusion;
+				next[self.index_unchecked(x, y)] -= spread;
+				for neighbour in neighbours.iter().flatten() {
+					next[*neighbour] += spread / num_neighbours as f32;
+				}
+			}
+		}
+		let retained = 1f32 - self.config.evaporation;
+		for value in next.iter_mut() {
+			*value = (*value * retained).clamp(0f32, 1f32);
+		}
+		self.values = next;
+	}
+
+	fn index(&self, position: &Position) -> Option<usize> {
+		if position.x < self.width && position.y < self.height {
+			Some(self.index_unchecked(position.x, position.y))
+		} else {
+			None
+		}
+	}
+
+	fn index_unchecked(&self, x: u16, y: u16) -> usize {
+		y as usize * self.width as usize + x as usize
+	}
+}
+
+#[test]
+fn should_deposit_and_saturate() {
+	let mut field = PheromoneField::init(
+		&Size {
+			width: 4,
+			height: 4,
+		},
+		Topology::Bounded,
+		&PheromoneConfig::default(),
+	);
+	let position = Position { x: 1, y: 2 };
+	field.deposit(&position, 1f32);
+	assert_eq!(field.get(&position), 0.5f32);
+	field.deposit(&position, 1f32);
+	field.deposit(&position, 1f32);
+	assert_eq!(field.get(&position), 1f32);
+	// a negative emission doesn't remove pheromone
+	field.deposit(&position, -1f32);
+	assert_eq!(field.get(&position), 1f32);
+	assert_eq!(field.get(&Position { x: 4, y: 0 }), 0f32);
+}
+
+#[test]
+fn should_diffuse_and_evaporate() {
+	let config = PheromoneConfig {
+		deposit: 1f32,
+		diffusion: 0.4f32,
+		evaporation: 0f32,
+	};
+	let mut field = PheromoneField::init(
+		&Size {
+			width: 3,
+			height: 3,
+		},
+		Topology::Bounded,
+		&config,
+	);
+	let center = Position { x: 1, y: 1 };
+	field.deposit(&center, 1f32);
+	field.diffuse_and_evaporate();
+	assert!((field.get(&center) - 0.6f32).abs() < 1e-6);
+	assert!((field.get(&Position { x: 1, y: 2 }) - 0.1f32).abs() < 1e-6);
+	assert_eq!(field.get(&Position { x: 0, y: 0 }), 0f32);
+	// without evaporation the pheromone is only moved around
+	let total: f32 = field.values.iter().sum();
+	assert!((total - 1f32).abs() < 1e-6);
+
+	let mut field = PheromoneField::init(
+		&Size {
+			width: 3,
+			height: 3,
+		},
+		Topology::Bounded,
+		&PheromoneConfig {
+			evaporation: 0.5f32,
+			..config
+		},
+	);
+	field.deposit(&center, 1f32);
+	field.diffuse_and_evaporate();
+	let total: f32 = field.values.iter().sum();
+	assert!((total - 0.5f32).abs() < 1e-6);
+
+	// in a toroidal world the pheromone spreads across the edges
+	let mut field = PheromoneField::init(
+		&Size {
+			width: 3,
+			height: 3,
+		},
+		Topology::Toroidal,
+		&config,
+	);
+	let corner = Position { x: 0, y: 0 };
+	field.deposit(&corner, 1f32);
+	field.diffuse_and_evaporate();
+	assert!((field.get(&Position { x: 2, y: 0 }) - 0.1f32).abs() < 1e-6);
+	assert!((field.get(&Position { x: 0, y: 2 }) - 0.1f32).abs() < 1e-6);
+}